@@ -1,5 +1,6 @@
 use std::f32::consts::PI;
 
+// Defaults of the user settings
 pub const CAM_SPEED: f32 = 20.; // speed of wasd movement
 pub const SHIFT_BOOST: f32 = 4.0; // how much more speeder holding shift
 pub const MOUSE_SENS: f32 = 0.001*2.*PI; // mouse sens of camera
@@ -7,4 +8,11 @@ pub const MOUSE_SENS: f32 = 0.001*2.*PI; // mouse sens of camera
 pub const BASE_WINDOW_X: f32 = 1200.;
 pub const BASE_WINDOW_Y: f32 = 900.;
 
-pub const MODE_SWITCH: bevy::prelude::KeyCode = bevy::prelude::KeyCode::Z;
\ No newline at end of file
+pub const AUTO_RELOAD_DELAY: f32 = 0.3; // seconds the transform settings have to stay put before voxelizing again
+
+pub const HISTORY_LEN: usize = 50; // undo steps kept, each one holds a copy of the voxels
+
+pub const MODE_SWITCH: bevy::prelude::KeyCode = bevy::prelude::KeyCode::Z;
+
+pub const SETTINGS_PATH: &str = "./settings.ron";
+pub const SAVE_DIALOG_PATH: &str = "./save_dialog.ron"; // last folder saved to and recent saves
\ No newline at end of file
@@ -1,8 +1,22 @@
-mod camera;
+pub mod camera;
+pub mod colouring;
+pub mod components;
+pub mod edit;
+pub mod files;
+pub mod history;
+pub mod measure;
+pub mod overhang;
+pub mod overlay;
+pub mod picking;
+pub mod project;
+pub mod watch;
 
-use bevy::{prelude::*, window::{CursorGrabMode, PrimaryWindow}};
+use std::sync::Arc;
 
-use crate::ui::UiState;
+use bevy::{prelude::*, tasks::{AsyncComputeTaskPool, Task}, window::{CursorGrabMode, PrimaryWindow}};
+use futures_lite::future;
+
+use crate::{consts, schematic::{Schematic, ShapeTransform, VoxelSources}, settings::{Action, Settings}, ui::UiState, voxel_grid::{Pos, VoxelGrid}, voxelization::Progress};
 
 pub struct GeneralPlugin;
 
@@ -10,15 +24,38 @@ impl Plugin for GeneralPlugin {
     fn build(&self, app: &mut App) {
         app
             .add_event::<ReloadVoxelsEvent>()
+            .add_event::<SchematicLoadedEvent>()
+            .add_event::<VoxelsReplacedEvent>()
+            .add_event::<RespawnVoxelsEvent>()
             .add_plugin(camera::CameraPlugin)
+            .add_plugin(colouring::ColouringPlugin)
+            .add_plugin(components::ComponentsPlugin)
+            .add_plugin(edit::EditPlugin)
+            .add_plugin(files::FilesPlugin)
+            .add_plugin(history::HistoryPlugin)
+            .add_plugin(measure::MeasurePlugin)
+            .add_plugin(overhang::OverhangPlugin)
+            .add_plugin(overlay::OverlayPlugin)
+            .add_plugin(picking::PickingPlugin)
+            .add_plugin(project::ProjectPlugin)
+            .add_plugin(watch::WatchPlugin)
             // .insert_resource(AppState::Ui)
             .add_state::<AppState>()
             .insert_resource(HandleHolder::default())
             .insert_resource(RotationConfig::default())
             .insert_resource(crate::schematic::Schematic::default())
+            .insert_resource(VoxelizeJob::default())
+            .insert_resource(Voxels::default())
             .add_startup_system(init_handles)
             .add_system(state_cycle_system)
-            .add_system(reload_voxel_system);
+            .add_system(auto_reload_system.before(reload_voxel_system).run_if(|ui_state: Res<UiState>| ui_state.auto_reload))
+            .add_system(reload_voxel_system)
+            .add_system(finish_voxelize_system.after(reload_voxel_system))
+            .add_system(spawn_voxels_system
+                .after(finish_voxelize_system)
+                .after(history::restore_system)
+                .run_if(on_event::<VoxelsReplacedEvent>().or_else(on_event::<RespawnVoxelsEvent>()))
+            );
         // app.add_system_to_stage(CoreStage::PreUpdate, state_cycle_system);
         // app.add_startup_system(spawn_debug_cube);
         // .add_system(exit_on_esc);
@@ -33,28 +70,60 @@ pub enum AppState {
     Camera
 }
 
-#[derive(Resource)]
+#[derive(Resource, Clone)]
 pub struct RotationConfig {
-    pub scale: f32,
+    pub scale: Vec3, // per axis, applied after rotation
+    pub mirror: (bool, bool, bool), // flips the shape along x, y, z
     pub rotx: f32,
     pub roty: f32,
     pub rotz: f32,
-    pub yrange: (i32, i32), // min, max
+    pub offset: Vec3, // translation in voxels, fractional values shift the model within a voxel
+    pub centre: CentreMode,
+    pub bounds: (Pos, Pos), // min, max of the last voxelization
     quat: Quat
 }
 
+// Where the model's bounding box centre gets moved to before the offset is applied
+#[derive(PartialEq, Eq, Debug, Default, Clone, Copy, serde::Serialize, serde::Deserialize)]
+pub enum CentreMode {
+    #[default]
+    None,
+    VoxelCentre,
+    VoxelCorner
+}
+
+// The voxels currently spawned, manual edits included, and the schematic and transform they were voxelized from
+#[derive(Resource, Default)]
+pub struct Voxels {
+    pub grid: VoxelGrid,
+    pub schematic: Arc<Schematic>, // differs from the loaded one while a newly loaded schematic is voxelizing
+    pub transform: ShapeTransform,
+    pub edits: edit::Edits,
+    pub sources: Option<Arc<VoxelSources>> // only kept while a colouring scheme needs them
+}
+
 #[derive(Resource, Default)]
 pub struct HandleHolder {
     cube: Handle<Mesh>,
-    materials: [Handle<StandardMaterial>; 8]
+    face: Handle<Image>,
+    preview: Handle<StandardMaterial>, // transparent, for the layers past the clip
+    colours: std::collections::HashMap<[u8; 3], Handle<StandardMaterial>> // filled by colouring as colours get used
 }
 
-fn state_cycle_system(mut state: ResMut<State<AppState>>, keyboard: Res<Input<KeyCode>>, mut windows: Query<&mut Window, With<PrimaryWindow>>) {
-    if keyboard.just_pressed(crate::consts::MODE_SWITCH) {
+fn state_cycle_system(
+    mut state: ResMut<State<AppState>>,
+    mut cam_mode: ResMut<camera::CameraMode>,
+    keyboard: Res<Input<KeyCode>>,
+    settings: Res<Settings>,
+    mut windows: Query<&mut Window, With<PrimaryWindow>>
+) {
+    // ctrl+z is undo
+    if settings.keys.just_pressed(&keyboard, Action::ModeSwitch) && !keyboard.any_pressed([KeyCode::LControl, KeyCode::RControl]) {
         let mut window = windows.get_single_mut().unwrap();
         match state.0 {
             AppState::Ui => {
                 state.0 = AppState::Camera;
+                if *cam_mode != camera::CameraMode::Fly {*cam_mode = camera::CameraMode::Fly;} // only the fly camera uses the captured cursor
                 window.cursor.grab_mode = CursorGrabMode::Locked;
                 window.cursor.visible = false;
                 // window.set_cursor_grab_mode();
@@ -84,23 +153,8 @@ fn init_handles (
     asset_server: Res<AssetServer>,
 ) {
     handles.cube = meshes.add(Mesh::from(shape::Cube { size: 1.0 }));
-    let texture = Some(asset_server.load("cubeface.png"));
-    for (i, c) in [
-        Color::RED,
-        Color::rgb(1.0, 0.5, 0.),
-        Color::YELLOW,
-        Color::GREEN,
-        Color::rgb(0., 0.5, 1.), // BLUE
-        Color::rgb(0.3, 0., 0.8), // ueue
-        Color::rgb(1.0, 0.1, 0.5), // aii
-    ].into_iter().enumerate() {
-        handles.materials[i] = materials.add(StandardMaterial {
-            base_color_texture: texture.clone(),
-            base_color: c,
-            ..default()
-        });
-    }
-    handles.materials[7] = materials.add(StandardMaterial {
+    handles.face = asset_server.load("cubeface.png");
+    handles.preview = materials.add(StandardMaterial {
         base_color_texture: Some(asset_server.load("tpface.png")),
         alpha_mode: AlphaMode::Blend,
         ..default()
@@ -109,67 +163,250 @@ fn init_handles (
 
 pub struct ReloadVoxelsEvent;
 
-fn reload_voxel_system (
-    mut commands: Commands,
-    handles: Res<HandleHolder>,
+// A different schematic was loaded, manual edits don't carry over to it
+pub struct SchematicLoadedEvent {
+    pub name: String, // file name, for the history
+    pub edits: edit::Edits // edits to start from instead, only projects have any
+}
+
+// A voxelization finished and replaced the voxels
+pub struct VoxelsReplacedEvent;
+
+// The voxels were set without voxelizing, e.g. going back in the history, and need spawning again
+pub struct RespawnVoxelsEvent;
+
+type VoxelizeTask = Task<Option<(VoxelGrid, Option<VoxelSources>)>>; // None if cancelled
+
+// Voxelization running on the async compute pool, the previous voxels stay spawned until it finishes
+#[derive(Resource)]
+pub struct VoxelizeJob {
+    task: Option<VoxelizeTask>,
+    schematic: Arc<Schematic>,
+    transform: ShapeTransform,
+    progress: Arc<Progress>,
+    keep_edits: bool, // false if a schematic was loaded since the last voxelization
+    edits: edit::Edits // what the edits become when they aren't kept
+}
+
+impl Default for VoxelizeJob {
+    fn default() -> Self {
+        Self {task: None, schematic: default(), transform: default(), progress: Arc::new(Progress::new(0)), keep_edits: true, edits: default()}
+    }
+}
+
+impl VoxelizeJob {
+    pub fn is_running(&self) -> bool {
+        self.task.is_some()
+    }
+
+    pub fn progress(&self) -> &Progress {
+        &self.progress
+    }
+
+    pub fn cancel(&self) {
+        self.progress.cancel();
+    }
+}
+
+// Sends a reload once the transform settings have stayed the same for AUTO_RELOAD_DELAY
+// Nothing happens if they end up giving the transform of the voxels shown or being made, e.g. after undo
+fn auto_reload_system(
+    time: Res<Time>,
     schematic: Res<crate::schematic::Schematic>,
+    rot_con: Res<RotationConfig>,
+    job: Res<VoxelizeJob>,
+    voxels: Res<Voxels>,
+    mut debounce: Local<(Option<RotationConfig>, f32)>, // last seen settings, seconds they've been unchanged
+    mut writer: EventWriter<ReloadVoxelsEvent>
+) {
+    let (last, still) = &mut *debounce;
+    match last {
+        Some(l) if l.same_settings(&rot_con) => {},
+        _ => {
+            *last = Some(rot_con.clone());
+            *still = 0.;
+            return;
+        },
+    }
+    if *still >= consts::AUTO_RELOAD_DELAY {
+        return; // already handled
+    }
+    *still += time.delta_seconds();
+    if *still < consts::AUTO_RELOAD_DELAY {
+        return;
+    }
+    let mut settings = rot_con.clone();
+    settings.compute_quat();
+    let transform = settings.transform(&schematic);
+    let pending = if job.is_running() {job.transform} else {voxels.transform};
+    if transform != pending {
+        writer.send(ReloadVoxelsEvent);
+    }
+}
+
+fn reload_voxel_system (
+    schematic: Res<Schematic>,
+    colouring: Res<colouring::Colouring>,
     mut rot_con: ResMut<RotationConfig>,
-    mut ui_state: ResMut<UiState>,
+    mut job: ResMut<VoxelizeJob>,
     mut reader: EventReader<ReloadVoxelsEvent>,
-    previous: Query<Entity, With<Voxel>>
+    mut loaded: EventReader<SchematicLoadedEvent>,
+    mut shared: Local<Option<Arc<Schematic>>> // one copy for all voxelizations of the same schematic, history keeps them
 ) {
+    if schematic.is_changed() {
+        *shared = None;
+    }
     if reader.is_empty() {
         return;
     }
     reader.clear();
 
-    for e in previous.iter() {
-        commands.entity(e).despawn();
+    // a job still running is superseded by this one, if it was for a newly loaded schematic so is this one
+    job.cancel();
+    match loaded.iter().last() {
+        Some(SchematicLoadedEvent {edits, ..}) => {
+            job.keep_edits = false;
+            job.edits = edits.clone();
+        },
+        None => job.keep_edits = job.keep_edits || !job.is_running(),
     }
 
     rot_con.compute_quat();
-    let voxels = schematic.voxelize_with_transform(rot_con.quat, rot_con.scale);
-    let mut miny = 100000; let mut maxy = -100000;
-    for (_, y, _) in voxels.iter() {if *y<miny {miny = *y;} if *y>maxy {maxy = *y;}} // quick minmax search
-    rot_con.yrange = (miny, maxy);
+    let transform = rot_con.transform(&schematic);
+    let schematic = shared.get_or_insert_with(|| Arc::new(schematic.clone())).clone();
+    job.schematic = schematic.clone();
+    let progress = Arc::new(Progress::new(schematic.work_units()));
+    let task_progress = progress.clone();
+    let sources = colouring.scheme.needs_sources();
+    job.task = Some(AsyncComputeTaskPool::get().spawn(async move {
+        if sources {
+            let sources = schematic.voxel_sources(&transform, &task_progress)?;
+            Some((sources.keys().copied().collect(), Some(sources)))
+        }
+        else {
+            Some((schematic.voxelize_with_progress(&transform, &task_progress)?, None))
+        }
+    }));
+    job.transform = transform;
+    job.progress = progress;
+}
+
+pub fn finish_voxelize_system (
+    mut rot_con: ResMut<RotationConfig>,
+    mut ui_state: ResMut<UiState>,
+    mut job: ResMut<VoxelizeJob>,
+    mut current: ResMut<Voxels>,
+    mut pending: ResMut<camera::PendingView>,
+    mut writer: EventWriter<VoxelsReplacedEvent>
+) {
+    match &job.task {
+        Some(task) if task.is_finished() => {},
+        _ => return,
+    }
+    let (mut voxels, sources) = match future::block_on(job.task.take().unwrap()) {
+        Some(done) => done,
+        None => {
+            pending.0 = None; // the camera of a project whose voxels won't show up
+            return; // cancelled, keep what's on screen
+        },
+    };
+
+    if !job.keep_edits || job.transform != current.transform {
+        current.edits = std::mem::take(&mut job.edits);
+    }
+    job.keep_edits = true;
+    current.edits.overlay(&mut voxels);
+
+    let bounds = voxels.bounds().unwrap_or(((0, 0, 0), (0, 0, 0)));
+    ui_state.fit_clip(rot_con.bounds, bounds);
+    rot_con.bounds = bounds;
     ui_state.voxel_count = voxels.len();
-    for p in voxels {
-        let mat_index = {
-            let diff = p.1 - ui_state.get_uptoy();
-            if diff > 2 {continue;}
-            else if diff > 0 {7}
-            else {(p.1.rem_euclid(14) / 2) as usize}
-        };
-        let v = Vec3::new(p.0 as f32, p.1 as f32, p.2 as f32);
-        commands.spawn((
-            PbrBundle {
-                mesh: handles.cube.clone(),
-                material: handles.materials[mat_index].clone(),
-                transform: Transform::from_translation(v),
-                ..default()
-            },
-            Voxel
-        ));
+    current.grid = voxels;
+    current.schematic = job.schematic.clone();
+    current.sources = sources.map(Arc::new);
+    current.transform = job.transform;
+    writer.send(VoxelsReplacedEvent);
+}
+
+fn spawn_voxels_system (
+    mut commands: Commands,
+    handles: Res<HandleHolder>,
+    current: Res<Voxels>,
+    previous: Query<Entity, With<Voxel>>
+) {
+    for e in previous.iter() {
+        commands.entity(e).despawn();
+    }
+    for p in current.grid.iter() {
+        spawn_voxel(&mut commands, &handles, p);
+    }
+}
+
+pub fn spawn_voxel(commands: &mut Commands, handles: &HandleHolder, p: Pos) {
+    commands.spawn((voxel_bundle(handles, p), Voxel(p)));
+}
+
+// Spawned hidden, colouring paints it and shows it unless it's clipped away
+pub fn voxel_bundle(handles: &HandleHolder, p: Pos) -> PbrBundle {
+    PbrBundle {
+        mesh: handles.cube.clone(),
+        material: handles.preview.clone(),
+        visibility: Visibility::Hidden,
+        transform: Transform::from_translation(Vec3::new(p.0 as f32, p.1 as f32, p.2 as f32)),
+        ..default()
     }
 }
 
 #[derive(Component)]
-pub struct Voxel;
+pub struct Voxel(pub Pos);
 
 impl Default for RotationConfig {
     fn default() -> Self {
-        Self {scale: 1.,
+        Self {scale: Vec3::ONE,
+            mirror: (false, false, false),
             rotx: 0.,
             roty: 0.,
             rotz: 0.,
-            yrange: (0, 0),
+            offset: Vec3::ZERO,
+            centre: CentreMode::None,
+            bounds: ((0, 0, 0), (0, 0, 0)),
             quat: default(),
         }
     }
 }
 
 impl RotationConfig {
+    // Everything that goes into the transform, bounds aren't a setting
+    fn same_settings(&self, other: &RotationConfig) -> bool {
+        (self.scale, self.mirror, self.rotx, self.roty, self.rotz, self.offset, self.centre)
+            == (other.scale, other.mirror, other.rotx, other.roty, other.rotz, other.offset, other.centre)
+    }
+
     pub fn compute_quat(&mut self) {
         self.quat = Quat::from_euler(EulerRot::YXZ, self.rotx, self.roty, self.rotz);
     }
+
+    // Scale with the mirror toggles folded in as negative components
+    pub fn scale_vec(&self) -> Vec3 {
+        let sign = |m: bool| if m {-1.} else {1.};
+        self.scale * Vec3::new(sign(self.mirror.0), sign(self.mirror.1), sign(self.mirror.2))
+    }
+
+    // Full transform for voxelizing, expects compute_quat to have been called
+    pub fn transform(&self, schematic: &crate::schematic::Schematic) -> ShapeTransform {
+        ShapeTransform {rot: self.quat, scale: self.scale_vec(), offset: self.translation(schematic)}
+    }
+
+    // Total translation applied after rotation and scaling, expects compute_quat to have been called
+    pub fn translation(&self, schematic: &crate::schematic::Schematic) -> Vec3 {
+        let target = match self.centre {
+            CentreMode::None => return self.offset,
+            CentreMode::VoxelCentre => Vec3::ZERO,
+            CentreMode::VoxelCorner => Vec3::splat(0.5),
+        };
+        match schematic.transformed_centre(self.quat, self.scale_vec()) {
+            Some(c) => target - c + self.offset,
+            None => self.offset,
+        }
+    }
 }
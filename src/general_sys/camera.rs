@@ -1,16 +1,34 @@
 use std::f32::consts::PI;
 
-use bevy::{prelude::*, input::mouse::MouseMotion, core_pipeline::clear_color::ClearColorConfig};
-use super::AppState;
-use crate::consts;
+use bevy::{prelude::*, input::mouse::{MouseMotion, MouseScrollUnit, MouseWheel}, core_pipeline::clear_color::ClearColorConfig, render::camera::ScalingMode};
+use bevy_egui::EguiContexts;
+use super::{AppState, Voxels, VoxelsReplacedEvent};
+use crate::settings::{Action, Settings};
 
 pub struct CameraPlugin;
 
 impl Plugin for CameraPlugin {
     fn build(&self, app: &mut App) {
         app
+            .insert_resource(CameraMode::default())
+            .insert_resource(PendingView::default())
+            .add_event::<ViewEvent>()
             .add_startup_system(spawn_cam)
             .add_system(camera_move_system.in_set(OnUpdate(AppState::Camera)))
+            .add_system(sync_camera_mode_system.run_if(resource_changed::<CameraMode>()))
+            .add_system(orbit_focus_system.run_if(on_event::<VoxelsReplacedEvent>()))
+            .add_system(orbit_camera_system
+                .in_set(OnUpdate(AppState::Ui))
+                .run_if(resource_equals(CameraMode::Orbit))
+                .after(orbit_focus_system)
+            )
+            .add_system(view_keys_system.in_set(OnUpdate(AppState::Ui)))
+            .add_system(view_event_system.after(view_keys_system).after(orbit_focus_system))
+            .add_system(restore_view_system
+                .after(orbit_focus_system)
+                .before(sync_camera_mode_system)
+                .run_if(on_event::<VoxelsReplacedEvent>())
+            )
             .insert_resource(AmbientLight {
                 color: Color::WHITE,
                 brightness: 1.1,
@@ -18,12 +36,137 @@ impl Plugin for CameraPlugin {
     }
 }
 
+#[derive(Resource, PartialEq, Eq, Debug, Default, Clone, Copy, serde::Serialize, serde::Deserialize)]
+pub enum CameraMode {
+    #[default]
+    Fly, // free movement, needs the cursor captured in AppState::Camera
+    Orbit // rotates around the voxels with the mouse in AppState::Ui
+}
+
 #[derive(Default, Component)]
 pub struct CamRotation {
     yaw: f32,
     pitch: f32
 }
 
+// The camera sits at focus + rotation*(0, 0, distance), rotation built from yaw and pitch like CamRotation
+#[derive(Component)]
+pub struct OrbitState {
+    focus: Vec3,
+    distance: f32,
+    yaw: f32,
+    pitch: f32
+}
+
+impl Default for OrbitState {
+    fn default() -> Self {
+        Self {focus: Vec3::ZERO, distance: 10., yaw: 0., pitch: 0.}
+    }
+}
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum ViewPreset {
+    Top,
+    Bottom,
+    Front,
+    Back,
+    Left,
+    Right,
+    Isometric
+}
+
+impl ViewPreset {
+    pub const ALL: [ViewPreset; 7] = [Self::Top, Self::Bottom, Self::Front, Self::Back, Self::Left, Self::Right, Self::Isometric];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Top => "Top",
+            Self::Bottom => "Bottom",
+            Self::Front => "Front",
+            Self::Back => "Back",
+            Self::Left => "Left",
+            Self::Right => "Right",
+            Self::Isometric => "Isometric",
+        }
+    }
+
+    // Orbit yaw and pitch looking at the model from this side, front looks along -Z
+    fn yaw_pitch(self) -> (f32, f32) {
+        match self {
+            Self::Top => (0., PI/2.),
+            Self::Bottom => (0., -PI/2.),
+            Self::Front => (0., 0.),
+            Self::Back => (PI, 0.),
+            Self::Left => (-PI/2., 0.),
+            Self::Right => (PI/2., 0.),
+            Self::Isometric => (PI/4., (0.5_f32).sqrt().atan()),
+        }
+    }
+}
+
+// Where the camera is and what it looks like, saved in projects
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct CameraView {
+    mode: CameraMode,
+    translation: Vec3,
+    rotation: Quat,
+    fly: (f32, f32), // yaw, pitch
+    orbit: (Vec3, f32, f32, f32), // focus, distance, yaw, pitch
+    ortho: Option<(f32, f32)> // height and zoom of an orthographic view
+}
+
+impl CameraView {
+    pub fn capture(mode: CameraMode, tf: &Transform, rot: &CamRotation, orbit: &OrbitState, projection: &Projection) -> Self {
+        let ortho = match projection {
+            Projection::Orthographic(o) => match o.scaling_mode {
+                ScalingMode::FixedVertical(height) => Some((height, o.scale)),
+                _ => Some((2., o.scale)),
+            },
+            Projection::Perspective(_) => None,
+        };
+        Self {
+            mode,
+            translation: tf.translation,
+            rotation: tf.rotation,
+            fly: (rot.yaw, rot.pitch),
+            orbit: (orbit.focus, orbit.distance, orbit.yaw, orbit.pitch),
+            ortho
+        }
+    }
+
+    fn apply(&self, tf: &mut Transform, rot: &mut CamRotation, orbit: &mut OrbitState, projection: &mut Projection) {
+        tf.translation = self.translation;
+        tf.rotation = self.rotation;
+        (rot.yaw, rot.pitch) = self.fly;
+        (orbit.focus, orbit.distance, orbit.yaw, orbit.pitch) = self.orbit;
+        *projection = match self.ortho {
+            Some((height, scale)) => Projection::Orthographic(OrthographicProjection {
+                far: 10000.,
+                scaling_mode: ScalingMode::FixedVertical(height),
+                scale,
+                ..default()
+            }),
+            None => Projection::Perspective(default()),
+        };
+    }
+}
+
+// Applied once the voxels it was saved with are ready, after the orbit camera refocused on them
+// Dropped if that voxelization is cancelled or another file is loaded first
+#[derive(Resource, Default)]
+pub struct PendingView(pub Option<CameraView>);
+
+pub enum ViewEvent {
+    Preset(ViewPreset), // orthographic view from a side, switches to the orbit camera
+    Perspective,
+    FrameModel // fits the voxels in view
+}
+
+fn yaw_pitch_rotation(yaw: f32, pitch: f32) -> Quat {
+    Quat::from_axis_angle(Vec3::Y, yaw) *
+    Quat::from_axis_angle(-Vec3::X, pitch)
+}
+
 pub fn spawn_cam(mut commands: Commands) {
     commands.spawn((
         Camera3dBundle {
@@ -34,7 +177,8 @@ pub fn spawn_cam(mut commands: Commands) {
             },
             ..default()
         },
-        CamRotation::default()
+        CamRotation::default(),
+        OrbitState::default()
     ));
     // commands.spawn(
     //     DirectionalLightBundle {
@@ -53,12 +197,12 @@ pub fn spawn_cam(mut commands: Commands) {
     // );
 }
 
-pub fn movement_axis<const N: usize>(input: &Res<Input<KeyCode>>, plus: [KeyCode; N], minus: [KeyCode; N]) -> f32 {
+pub fn movement_axis(input: &Input<KeyCode>, settings: &Settings, plus: Action, minus: Action) -> f32 {
 	let mut axis = 0.0;
-	if input.any_pressed(plus) {
+	if settings.keys.pressed(input, plus) {
 		axis += 1.0;
 	}
-	if input.any_pressed(minus) {
+	if settings.keys.pressed(input, minus) {
 		axis -= 1.0;
 	}
 	axis
@@ -68,6 +212,7 @@ pub fn camera_move_system(
     mut ev_motion: EventReader<MouseMotion>,
     mut query: Query<(&mut Transform, &mut CamRotation)>,
     keyboard: Res<Input<KeyCode>>,
+    settings: Res<Settings>,
     time: Res<Time>
 ) {
     let mut m_delta = Vec2::ZERO;
@@ -75,7 +220,7 @@ pub fn camera_move_system(
     for e in ev_motion.iter() {
         m_delta += e.delta;
     }
-    m_delta*=consts::MOUSE_SENS;
+    m_delta*=settings.mouse_sens;
 
     rot.pitch += m_delta.y;
     rot.yaw -= m_delta.x;
@@ -83,16 +228,171 @@ pub fn camera_move_system(
     if rot.yaw > 2.*PI {rot.yaw-=2.*PI;}
     if rot.yaw < -2.*PI {rot.yaw+=2.*PI;}
 
-    tf.rotation = 
-        Quat::from_axis_angle(Vec3::Y, rot.yaw) *
-		Quat::from_axis_angle(-Vec3::X, rot.pitch);
+    tf.rotation = yaw_pitch_rotation(rot.yaw, rot.pitch);
 
-    let mut movespeed = consts::CAM_SPEED;
-    if keyboard.pressed(KeyCode::LShift) {movespeed*=consts::SHIFT_BOOST;}
+    let mut movespeed = settings.cam_speed;
+    if settings.keys.pressed(&keyboard, Action::Boost) {movespeed*=settings.shift_boost;}
 
-    let lr = movement_axis(&keyboard, [KeyCode::D, KeyCode::Right], [KeyCode::A, KeyCode::Left]);
-    let fb = movement_axis(&keyboard, [KeyCode::S, KeyCode::Down], [KeyCode::W, KeyCode::Up]);
-    let ud = movement_axis(&keyboard, [KeyCode::Space], [KeyCode::LControl]);
+    let lr = movement_axis(&keyboard, &settings, Action::Right, Action::Left);
+    let fb = movement_axis(&keyboard, &settings, Action::Back, Action::Forward);
+    let ud = movement_axis(&keyboard, &settings, Action::Up, Action::Down);
     let k_delta = tf.rotation.mul_vec3(Vec3::new(lr, ud, fb))*movespeed*time.delta_seconds();
     tf.translation += k_delta;
-}
\ No newline at end of file
+}
+
+// Carries the current view over when switching modes, so the camera doesn't jump
+fn sync_camera_mode_system(mode: Res<CameraMode>, mut query: Query<(&mut Transform, &mut CamRotation, &mut OrbitState, &mut Projection)>) {
+    let (mut tf, mut rot, mut orbit, mut projection) = query.single_mut();
+    match *mode {
+        CameraMode::Fly => {
+            let (yaw, x, _) = tf.rotation.to_euler(EulerRot::YXZ);
+            rot.yaw = yaw;
+            rot.pitch = -x;
+            if let Projection::Orthographic(_) = *projection {
+                *projection = Projection::Perspective(default());
+            }
+        },
+        CameraMode::Orbit => {
+            let offset = tf.translation - orbit.focus;
+            orbit.distance = offset.length().max(1.);
+            let dir = offset.normalize_or_zero();
+            orbit.pitch = dir.y.clamp(-1., 1.).asin();
+            if dir.x.abs() + dir.z.abs() > 0.0001 { // yaw is undefined looking straight up or down, keep the old one
+                orbit.yaw = dir.x.atan2(dir.z);
+            }
+            update_orbit_transform(&orbit, &mut tf);
+        },
+    }
+}
+
+// Orbit around the centre of the voxels whenever they're replaced, editing single voxels doesn't move the camera
+fn orbit_focus_system(voxels: Res<Voxels>, mut query: Query<&mut OrbitState>) {
+    if let Some((min, max)) = voxels.grid.bounds() {
+        let min = Vec3::new(min.0 as f32, min.1 as f32, min.2 as f32);
+        let max = Vec3::new(max.0 as f32, max.1 as f32, max.2 as f32);
+        query.single_mut().focus = (min + max) / 2.;
+    }
+}
+
+fn restore_view_system(
+    mut pending: ResMut<PendingView>,
+    mut cam_mode: ResMut<CameraMode>,
+    mut query: Query<(&mut Transform, &mut CamRotation, &mut OrbitState, &mut Projection)>
+) {
+    let Some(view) = pending.0.take() else {return;};
+    let (mut tf, mut rot, mut orbit, mut projection) = query.single_mut();
+    view.apply(&mut tf, &mut rot, &mut orbit, &mut projection);
+    if *cam_mode != view.mode {*cam_mode = view.mode;}
+}
+
+fn update_orbit_transform(orbit: &OrbitState, tf: &mut Transform) {
+    tf.rotation = yaw_pitch_rotation(orbit.yaw, orbit.pitch);
+    tf.translation = orbit.focus + tf.rotation.mul_vec3(Vec3::Z*orbit.distance);
+}
+
+// Left drag rotates, middle drag pans, wheel zooms, ignores the mouse while it's over the ui
+pub fn orbit_camera_system(
+    mut ctx: EguiContexts,
+    mut ev_motion: EventReader<MouseMotion>,
+    mut ev_wheel: EventReader<MouseWheel>,
+    mouse: Res<Input<MouseButton>>,
+    settings: Res<Settings>,
+    mut query: Query<(&mut Transform, &mut OrbitState, &mut Projection)>
+) {
+    let mut m_delta = Vec2::ZERO;
+    for e in ev_motion.iter() {
+        m_delta += e.delta;
+    }
+    let mut scroll = 0.;
+    for e in ev_wheel.iter() {
+        scroll += match e.unit {
+            MouseScrollUnit::Line => e.y,
+            MouseScrollUnit::Pixel => e.y/100.,
+        };
+    }
+    let ctx = ctx.ctx_mut();
+    if ctx.is_pointer_over_area() || ctx.is_using_pointer() {
+        return;
+    }
+
+    let (mut tf, mut orbit, mut projection) = query.single_mut();
+    if mouse.pressed(MouseButton::Left) {
+        orbit.yaw -= m_delta.x*settings.mouse_sens;
+        orbit.pitch += m_delta.y*settings.mouse_sens;
+        orbit.pitch = orbit.pitch.clamp(-PI/2., PI/2.);
+    }
+    if mouse.pressed(MouseButton::Middle) {
+        let pan = tf.rotation.mul_vec3(Vec3::new(-m_delta.x, m_delta.y, 0.))*orbit.distance*0.002;
+        orbit.focus += pan;
+    }
+    match projection.as_mut() {
+        Projection::Orthographic(ortho) => ortho.scale *= 0.9_f32.powf(scroll),
+        Projection::Perspective(_) => orbit.distance = (orbit.distance*0.9_f32.powf(scroll)).max(1.),
+    }
+
+    update_orbit_transform(&orbit, &mut tf);
+}
+
+// 1-7 pick a view preset in ViewPreset::ALL order, 0 goes back to perspective, F frames the model
+fn view_keys_system(mut ctx: EguiContexts, keyboard: Res<Input<KeyCode>>, mut writer: EventWriter<ViewEvent>) {
+    if ctx.ctx_mut().wants_keyboard_input() {
+        return;
+    }
+    let keys = [KeyCode::Key1, KeyCode::Key2, KeyCode::Key3, KeyCode::Key4, KeyCode::Key5, KeyCode::Key6, KeyCode::Key7];
+    for (key, preset) in keys.into_iter().zip(ViewPreset::ALL) {
+        if keyboard.just_pressed(key) {
+            writer.send(ViewEvent::Preset(preset));
+        }
+    }
+    if keyboard.just_pressed(KeyCode::Key0) {
+        writer.send(ViewEvent::Perspective);
+    }
+    if keyboard.just_pressed(KeyCode::F) {
+        writer.send(ViewEvent::FrameModel);
+    }
+}
+
+fn view_event_system(
+    mut events: EventReader<ViewEvent>,
+    mut cam_mode: ResMut<CameraMode>,
+    voxels: Res<Voxels>,
+    mut query: Query<(&mut Transform, &mut OrbitState, &mut Projection)>
+) {
+    let (mut tf, mut orbit, mut projection) = query.single_mut();
+    for event in events.iter() {
+        match event {
+            ViewEvent::Preset(preset) => {
+                (orbit.yaw, orbit.pitch) = preset.yaw_pitch();
+                if let Projection::Perspective(_) = *projection {
+                    *projection = Projection::Orthographic(OrthographicProjection {
+                        far: 10000.,
+                        scaling_mode: ScalingMode::FixedVertical(2.*orbit.distance*(PI/8.).tan()), // same size as the default perspective
+                        ..default()
+                    });
+                }
+            },
+            ViewEvent::Perspective => {
+                *projection = Projection::Perspective(default());
+            },
+            ViewEvent::FrameModel => {
+                let Some((min, max)) = voxels.grid.bounds() else {continue;};
+                let min = Vec3::new(min.0 as f32, min.1 as f32, min.2 as f32) - 0.5;
+                let max = Vec3::new(max.0 as f32, max.1 as f32, max.2 as f32) + 0.5;
+                let radius = (max - min).length() / 2.;
+                orbit.focus = (min + max) / 2.;
+                match projection.as_mut() {
+                    Projection::Orthographic(ortho) => {
+                        ortho.scaling_mode = ScalingMode::FixedVertical(radius*2.2);
+                        ortho.scale = 1.;
+                        orbit.distance = radius*2. + 1.; // only needs to be outside the model
+                    },
+                    Projection::Perspective(persp) => {
+                        orbit.distance = radius*1.1 / (persp.fov/2.).sin();
+                    },
+                }
+            },
+        }
+        if *cam_mode != CameraMode::Orbit {*cam_mode = CameraMode::Orbit;}
+        update_orbit_transform(&orbit, &mut tf);
+    }
+}
@@ -0,0 +1,295 @@
+use std::sync::Arc;
+
+use bevy::{prelude::*, ecs::system::SystemParam, render::view::VisibilitySystems, tasks::{AsyncComputeTaskPool, Task}};
+use futures_lite::future;
+
+use super::{HandleHolder, Voxel, VoxelizeJob, Voxels, components::Components, overhang::{Overhangs, ScaffoldVoxel}};
+use crate::{schematic::{Schematic, ShapeTransform, VoxelSources}, voxelization::Progress, ui::{AxisClip, ClipState, UiState}, voxel_grid::{Pos, VoxelGrid}};
+
+const NO_SOURCE: [f32; 3] = [0.6, 0.6, 0.6]; // voxels added by hand in the source schemes, and elements without a group or material
+const INSIDE: [f32; 3] = [0.4, 0.4, 0.4]; // voxels without an empty neighbour in the normal scheme
+const OVERHANG: [f32; 3] = [1., 0., 1.]; // unsupported voxels while highlighted, over any scheme
+
+pub struct ColouringPlugin;
+
+impl Plugin for ColouringPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .insert_resource(Colouring::default())
+            .insert_resource(SourcesJob::default())
+            .add_system(finish_sources_system)
+            .add_system(request_sources_system.after(finish_sources_system))
+            // voxels spawned during Update exist by now, and get painted before visibility is worked out
+            .add_system(paint_voxels_system
+                .in_base_set(CoreSet::PostUpdate)
+                .before(VisibilitySystems::VisibilityPropagate)
+            );
+    }
+}
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum Scheme {
+    Layers, // bands of `band` layers going through the palette
+    Height, // gradient from the lowest to the highest layer
+    Normal, // direction of the empty neighbours
+    Element, // source element, going through the palette
+    Group,
+    Material,
+    Component, // connected part, going through the palette from the largest
+    Single
+}
+
+impl Scheme {
+    pub const ALL: [Scheme; 8] = [Self::Layers, Self::Height, Self::Normal, Self::Element, Self::Group, Self::Material, Self::Component, Self::Single];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Layers => "By layer",
+            Self::Height => "Height gradient",
+            Self::Normal => "Normal direction",
+            Self::Element => "By element",
+            Self::Group => "By group",
+            Self::Material => "By material",
+            Self::Component => "By component",
+            Self::Single => "Single colour",
+        }
+    }
+
+    // Needs Voxels::sources, which only a voxelization can give
+    pub fn needs_sources(self) -> bool {
+        matches!(self, Self::Element | Self::Group | Self::Material)
+    }
+}
+
+#[derive(Resource, Clone, PartialEq, Debug)]
+pub struct Colouring {
+    pub scheme: Scheme,
+    pub band: i32, // layers per colour in Scheme::Layers
+    pub palette: Vec<[f32; 3]>, // used by layers, elements, groups and materials
+    pub low: [f32; 3], // ends of the height gradient
+    pub high: [f32; 3],
+    pub single: [f32; 3],
+    pub scaffold: [f32; 3] // scaffolding is always this, whatever the scheme
+}
+
+impl Default for Colouring {
+    fn default() -> Self {
+        Self {
+            scheme: Scheme::Layers,
+            band: 2,
+            palette: vec![
+                [1., 0., 0.],
+                [1., 0.5, 0.],
+                [1., 1., 0.],
+                [0., 1., 0.],
+                [0., 0.5, 1.], // BLUE
+                [0.3, 0., 0.8], // ueue
+                [1., 0.1, 0.5], // aii
+            ],
+            low: [0., 0.2, 0.8],
+            high: [1., 0.9, 0.2],
+            single: [0.8, 0.8, 0.8],
+            scaffold: [0.9, 0.75, 0.4]
+        }
+    }
+}
+
+impl Colouring {
+    fn palette_colour(&self, i: i64) -> [f32; 3] {
+        if self.palette.is_empty() {return self.single;}
+        self.palette[i.rem_euclid(self.palette.len() as i64) as usize]
+    }
+
+    // Sources index into the schematic the voxels were made from, not the loaded one
+    fn colour(&self, voxels: &Voxels, components: &Components, yrange: (i32, i32), p: Pos) -> [f32; 3] {
+        let source = || voxels.sources.as_ref().and_then(|s| s.get(&p).copied());
+        match self.scheme {
+            Scheme::Layers => self.palette_colour(p.1.div_euclid(self.band.max(1)) as i64),
+            Scheme::Height => {
+                let t = if yrange.1 > yrange.0 {(p.1 - yrange.0) as f32 / (yrange.1 - yrange.0) as f32} else {0.};
+                let (low, high) = (Vec3::from(self.low), Vec3::from(self.high));
+                low.lerp(high, t).into()
+            },
+            Scheme::Normal => match normal(&voxels.grid, p) {
+                Some(n) => (n*0.5 + 0.5).into(),
+                None => INSIDE,
+            },
+            Scheme::Element => source().map_or(NO_SOURCE, |i| self.palette_colour(i as i64)),
+            Scheme::Group => source().and_then(|i| voxels.schematic.group_of(i)).map_or(NO_SOURCE, |g| self.palette_colour(g as i64)),
+            Scheme::Material => source().and_then(|i| voxels.schematic.material_of(i)).map_or(NO_SOURCE, |m| self.palette_colour(m as i64)),
+            Scheme::Component => components.of(p).map_or(NO_SOURCE, |c| self.palette_colour(c as i64)),
+            Scheme::Single => self.single,
+        }
+    }
+}
+
+// Average direction towards the empty neighbours, None if there aren't any
+fn normal(grid: &VoxelGrid, p: Pos) -> Option<Vec3> {
+    let mut n = Vec3::ZERO;
+    for (dx, dy, dz) in [(1, 0, 0), (-1, 0, 0), (0, 1, 0), (0, -1, 0), (0, 0, 1), (0, 0, -1)] {
+        if !grid.contains((p.0 + dx, p.1 + dy, p.2 + dz)) {
+            n += Vec3::new(dx as f32, dy as f32, dz as f32);
+        }
+    }
+    // opposite faces both open, like in a one voxel thick wall, cancel out
+    if n == Vec3::ZERO {None} else {Some(n.normalize())}
+}
+
+// Finds where the shown voxels came from in the background, from the schematic and transform they were made with
+// Unlike voxelizing again it leaves the edits alone and doesn't apply transform settings that weren't yet
+#[derive(Resource, Default)]
+struct SourcesJob(Option<(Task<Option<VoxelSources>>, Arc<Schematic>, ShapeTransform)>);
+
+// The source schemes need to know where voxels come from, voxels from a voxelization made without or from the history may not
+fn request_sources_system(colouring: Res<Colouring>, voxels: Res<Voxels>, voxelize: Res<VoxelizeJob>, mut job: ResMut<SourcesJob>) {
+    // a running voxelization finds them itself if the scheme needed them when it started, otherwise this runs after it
+    if job.0.is_some() || voxelize.is_running() || !colouring.scheme.needs_sources() || voxels.sources.is_some() || voxels.grid.is_empty() {
+        return;
+    }
+    let (schematic, transform) = (voxels.schematic.clone(), voxels.transform);
+    let task_schematic = schematic.clone();
+    let task = AsyncComputeTaskPool::get().spawn(async move {
+        task_schematic.voxel_sources(&transform, &Progress::new(task_schematic.work_units()))
+    });
+    job.0 = Some((task, schematic, transform));
+}
+
+fn finish_sources_system(mut job: ResMut<SourcesJob>, mut voxels: ResMut<Voxels>) {
+    match &job.0 {
+        Some((task, ..)) if task.is_finished() => {},
+        _ => return,
+    }
+    let (task, schematic, transform) = job.0.take().unwrap();
+    let sources = future::block_on(task);
+    // voxels replaced since by other ones get theirs from the next request
+    if Arc::ptr_eq(&schematic, &voxels.schematic) && transform == voxels.transform {
+        voxels.sources = sources.map(Arc::new);
+    }
+}
+
+// One material per colour, shared by all voxels of that colour
+#[derive(SystemParam)]
+pub(super) struct VoxelMaterials<'w> {
+    handles: ResMut<'w, HandleHolder>,
+    materials: ResMut<'w, Assets<StandardMaterial>>
+}
+
+impl VoxelMaterials<'_> {
+    fn get(&mut self, colour: [f32; 3]) -> Handle<StandardMaterial> {
+        let key = colour.map(|c| (c.clamp(0., 1.)*255.).round() as u8);
+        let HandleHolder {face, colours, ..} = self.handles.as_mut();
+        colours.entry(key).or_insert_with(|| self.materials.add(StandardMaterial {
+            base_color_texture: Some(face.clone()),
+            base_color: Color::rgb_u8(key[0], key[1], key[2]),
+            ..default()
+        })).clone()
+    }
+}
+
+type Paintable<'a> = (&'a mut Handle<StandardMaterial>, &'a mut Visibility);
+
+// Everything a voxel's colour depends on
+#[derive(SystemParam)]
+pub(super) struct PaintInputs<'w> {
+    colouring: Res<'w, Colouring>,
+    voxels: Res<'w, Voxels>,
+    overhangs: Res<'w, Overhangs>,
+    components: Res<'w, Components>
+}
+
+impl PaintInputs<'_> {
+    fn is_changed(&self) -> bool {
+        self.colouring.is_changed() || self.voxels.is_changed() || self.overhangs.is_changed() || self.components.is_changed()
+    }
+}
+
+// Everything is painted again when the voxels, the colouring or the clipping change, otherwise only new voxels are
+pub(super) fn paint_voxels_system(
+    mut materials: VoxelMaterials,
+    ui_state: Res<UiState>,
+    inputs: PaintInputs,
+    mut shown: Local<Option<([AxisClip; 3], bool, bool)>>,
+    mut query: Query<(Ref<Voxel>, Paintable)>,
+    mut scaffold: Query<(Ref<ScaffoldVoxel>, Paintable), Without<Voxel>>
+) {
+    let PaintInputs {colouring, voxels, overhangs, components} = &inputs;
+    let settings = (ui_state.clip_settings(), ui_state.show_overhangs, ui_state.stale);
+    let all = *shown != Some(settings) || inputs.is_changed();
+    *shown = Some(settings);
+    if colouring.is_changed() {
+        materials.handles.colours.clear(); // unused ones get freed once no voxel holds them
+    }
+    let yrange = voxels.grid.yrange().unwrap_or((0, 0));
+    // stale voxels are all see-through, so they don't pass for the file's current contents
+    let state = |p| match ui_state.clip_state(p) {
+        ClipState::Visible if ui_state.stale => ClipState::Preview,
+        s => s,
+    };
+    for (voxel, (material, visibility)) in query.iter_mut() {
+        if !all && !voxel.is_added() {
+            continue;
+        }
+        let p = voxel.0;
+        let colour = || if ui_state.show_overhangs && overhangs.0.contains(p) {OVERHANG} else {colouring.colour(voxels, components, yrange, p)};
+        paint(&mut materials, state(p), colour, material, visibility);
+    }
+    for (voxel, (material, visibility)) in scaffold.iter_mut() {
+        if all || voxel.is_added() {
+            paint(&mut materials, state(voxel.0), || colouring.scaffold, material, visibility);
+        }
+    }
+}
+
+// The colour is only worked out for voxels that aren't clipped
+fn paint(
+    materials: &mut VoxelMaterials,
+    state: ClipState,
+    colour: impl FnOnce() -> [f32; 3],
+    mut material: Mut<Handle<StandardMaterial>>,
+    mut visibility: Mut<Visibility>
+) {
+    let (m, v) = match state {
+        ClipState::Hidden => (None, Visibility::Hidden),
+        ClipState::Preview => (Some(materials.handles.preview.clone()), Visibility::Inherited),
+        ClipState::Visible => (Some(materials.get(colour())), Visibility::Inherited),
+    };
+    if let Some(m) = m {
+        if *material != m {*material = m;}
+    }
+    if *visibility != v {*visibility = v;}
+}
+
+
+#[cfg(test)]
+mod tests {
+    use bevy::prelude::Vec3;
+    use crate::{general_sys::{Voxels, components::{Components, Connectivity}}, voxel_grid::VoxelGrid};
+    use super::{normal, Colouring, Scheme, NO_SOURCE};
+
+    #[test]
+    fn layer_bands_and_normals() {
+        let c = Colouring {band: 3, palette: vec![[0., 0., 0.], [1., 1., 1.]], ..Default::default()};
+        let voxels = Voxels::default();
+        let parts = Components::default();
+        let colours: Vec<_> = (-3..6).map(|y| c.colour(&voxels, &parts, (0, 0), (0, y, 0))[0]).collect();
+        assert_eq!(colours, vec![1., 1., 1., 0., 0., 0., 1., 1., 1.]);
+
+        let c = Colouring {scheme: Scheme::Height, ..c};
+        assert_eq!(c.colour(&voxels, &parts, (0, 10), (0, 5, 0)), (Vec3::from(c.low)*0.5 + Vec3::from(c.high)*0.5).to_array());
+        // hand made voxels have no source
+        let c = Colouring {scheme: Scheme::Element, ..c};
+        assert_eq!(c.colour(&voxels, &parts, (0, 0), (0, 0, 0)), NO_SOURCE);
+        // the largest component gets the first palette colour
+        let grid: VoxelGrid = [(0, 0, 0), (1, 0, 0), (5, 0, 0)].into_iter().collect();
+        let parts = Components::label(&grid, Connectivity::Faces);
+        let c = Colouring {scheme: Scheme::Component, ..c};
+        assert_eq!(c.colour(&voxels, &parts, (0, 0), (1, 0, 0)), c.palette[0]);
+        assert_eq!(c.colour(&voxels, &parts, (0, 0), (5, 0, 0)), c.palette[1]);
+
+        let grid: VoxelGrid = [(0, 0, 0), (1, 0, 0), (0, 1, 0), (0, 0, 1), (-1, 0, 0), (0, -1, 0), (0, 0, -1)].into_iter().collect();
+        assert_eq!(normal(&grid, (0, 0, 0)), None);
+        assert_eq!(normal(&grid, (0, 1, 0)), Some(Vec3::Y));
+        // a single voxel has every face open
+        assert_eq!(normal(&grid, (5, 5, 5)), None);
+    }
+}
@@ -0,0 +1,152 @@
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+
+use super::{Voxels, colouring::{self, Colouring, Scheme}};
+use crate::{ui::UiState, voxel_grid::{Pos, VoxelGrid}};
+
+pub struct ComponentsPlugin;
+
+impl Plugin for ComponentsPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .insert_resource(Components::default())
+            // like the overhangs, after the voxels change in Update and before they're painted
+            .add_system(components_system.in_base_set(CoreSet::PostUpdate).before(colouring::paint_voxels_system));
+    }
+}
+
+// Which neighbours count as touching
+#[derive(PartialEq, Eq, Debug, Default, Clone, Copy)]
+pub enum Connectivity {
+    #[default]
+    Faces, // 6 neighbours
+    Edges, // 18
+    Corners // 26
+}
+
+impl Connectivity {
+    pub const ALL: [Connectivity; 3] = [Self::Faces, Self::Edges, Self::Corners];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Faces => "6, faces",
+            Self::Edges => "18, faces and edges",
+            Self::Corners => "26, faces, edges and corners",
+        }
+    }
+
+    fn offsets(self) -> Vec<Pos> {
+        // how many axes a neighbour can be off by
+        let max = match self {Self::Faces => 1, Self::Edges => 2, Self::Corners => 3};
+        let mut ret = Vec::new();
+        for x in -1..=1 {
+            for y in -1..=1 {
+                for z in -1..=1 {
+                    let n = i32::abs(x) + i32::abs(y) + i32::abs(z);
+                    if n > 0 && n <= max {ret.push((x, y, z));}
+                }
+            }
+        }
+        ret
+    }
+}
+
+// Connected parts of the voxels, numbered from the largest
+#[derive(Resource, Default)]
+pub struct Components {
+    labels: HashMap<Pos, usize>, // index into sizes
+    sizes: Vec<usize>
+}
+
+impl Components {
+    pub fn label(grid: &VoxelGrid, connectivity: Connectivity) -> Self {
+        let offsets = connectivity.offsets();
+        let mut seen = VoxelGrid::new();
+        let mut parts = Vec::new();
+        for start in grid.iter() {
+            if !seen.insert(start) {
+                continue;
+            }
+            // flood fill, the part doubles as the queue
+            let mut part = vec![start];
+            let mut i = 0;
+            while let Some(&p) = part.get(i) {
+                i += 1;
+                for o in offsets.iter() {
+                    let q = (p.0 + o.0, p.1 + o.1, p.2 + o.2);
+                    if grid.contains(q) && seen.insert(q) {part.push(q);}
+                }
+            }
+            parts.push(part);
+        }
+        parts.sort_by_key(|part| std::cmp::Reverse(part.len()));
+
+        let mut ret = Self::default();
+        for (i, part) in parts.into_iter().enumerate() {
+            ret.sizes.push(part.len());
+            ret.labels.extend(part.into_iter().map(|p| (p, i)));
+        }
+        ret
+    }
+
+    pub fn of(&self, p: Pos) -> Option<usize> {
+        self.labels.get(&p).copied()
+    }
+
+    // Largest first
+    pub fn sizes(&self) -> &[usize] {
+        &self.sizes
+    }
+
+    // Voxels of the components with less than n voxels
+    pub fn smaller_than(&self, n: usize) -> impl Iterator<Item = Pos> + '_ {
+        self.labels.iter().filter(move |(_, &i)| self.sizes[i] < n).map(|(&p, _)| p)
+    }
+}
+
+// Only while something shows them, labelling every voxel again on each edit is too slow for big grids to do all the time
+fn components_system(
+    voxels: Res<Voxels>,
+    ui_state: Res<UiState>,
+    colouring: Res<Colouring>,
+    mut components: ResMut<Components>,
+    mut computed: Local<Option<Connectivity>> // None while nothing shows them, so they're labelled once something does
+) {
+    if !ui_state.componentwindow_open() && colouring.scheme != Scheme::Component {
+        // nothing's coloured by them, so no need to paint again
+        if computed.take().is_some() {*components.bypass_change_detection() = Components::default();}
+        return;
+    }
+    let connectivity = ui_state.connectivity;
+    if !voxels.is_changed() && *computed == Some(connectivity) {
+        return;
+    }
+    *computed = Some(connectivity);
+    *components = Components::label(&voxels.grid, connectivity);
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::voxel_grid::VoxelGrid;
+    use super::{Components, Connectivity};
+
+    #[test]
+    fn connectivity_sizes() {
+        // a line of three, one voxel touching its end by an edge, and one touching that by a corner
+        let grid: VoxelGrid = [(0, 0, 0), (1, 0, 0), (2, 0, 0), (3, 1, 0), (4, 2, 1), (9, 9, 9)].into_iter().collect();
+        let sizes = |c| Components::label(&grid, c).sizes().to_vec();
+        assert_eq!(sizes(Connectivity::Faces), vec![3, 1, 1, 1]);
+        assert_eq!(sizes(Connectivity::Edges), vec![4, 1, 1]);
+        assert_eq!(sizes(Connectivity::Corners), vec![5, 1]);
+
+        let c = Components::label(&grid, Connectivity::Edges);
+        assert_eq!(c.of((3, 1, 0)), c.of((0, 0, 0)));
+        assert_ne!(c.of((4, 2, 1)), c.of((0, 0, 0)));
+        assert_eq!(c.of((5, 5, 5)), None);
+        let mut small: Vec<_> = c.smaller_than(2).collect();
+        small.sort();
+        assert_eq!(small, vec![(4, 2, 1), (9, 9, 9)]);
+    }
+}
@@ -0,0 +1,156 @@
+use bevy::prelude::*;
+
+use super::{AppState, HandleHolder, RotationConfig, Voxel, Voxels, picking::{self, Picked, Tool}};
+use crate::{ui::UiState, voxel_grid::{Pos, VoxelGrid}};
+
+pub struct EditPlugin;
+
+impl Plugin for EditPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_event::<EditEvent>()
+            .add_system(edit_click_system.in_set(OnUpdate(AppState::Ui)).after(picking::pick_system))
+            .add_system(edit_system.after(edit_click_system));
+    }
+}
+
+// Fills or clears a voxel, undoing it goes through the history
+pub struct EditEvent {
+    pub pos: Pos,
+    pub filled: bool
+}
+
+// Voxels added and removed by hand on top of the voxelized schematic
+// Kept through re-voxelization as long as the transform and the loaded schematic stay the same
+#[derive(Default, Clone, PartialEq, Eq)]
+pub struct Edits {
+    added: VoxelGrid,
+    removed: VoxelGrid
+}
+
+impl Edits {
+    pub fn from_voxels(added: impl IntoIterator<Item = Pos>, removed: impl IntoIterator<Item = Pos>) -> Self {
+        Self {added: added.into_iter().collect(), removed: removed.into_iter().collect()}
+    }
+
+    pub fn added(&self) -> impl Iterator<Item = Pos> + '_ {
+        self.added.iter()
+    }
+
+    pub fn removed(&self) -> impl Iterator<Item = Pos> + '_ {
+        self.removed.iter()
+    }
+
+    // Voxels that differ from the voxelized schematic
+    pub fn len(&self) -> usize {
+        self.added.len() + self.removed.len()
+    }
+
+    // Applies the edits to a freshly voxelized grid
+    pub fn overlay(&self, grid: &mut VoxelGrid) {
+        grid.union_with(&self.added);
+        grid.difference_with(&self.removed);
+    }
+
+    // Returns false if the voxel already was that way
+    pub fn apply(&mut self, grid: &mut VoxelGrid, p: Pos, filled: bool) -> bool {
+        if grid.contains(p) == filled {
+            return false;
+        }
+        // reverting an edit takes it out of the overlay instead of adding the opposite one
+        if filled {
+            grid.insert(p);
+            if !self.removed.remove(p) {self.added.insert(p);}
+        }
+        else {
+            grid.remove(p);
+            if !self.added.remove(p) {self.removed.insert(p);}
+        }
+        true
+    }
+}
+
+// Add fills the voxel in front of the clicked face, remove clears the clicked voxel
+fn edit_click_system(picked: Res<Picked>, mut writer: EventWriter<EditEvent>) {
+    let Some(p) = picked.hovered else {return;};
+    if !picked.clicked {
+        return;
+    }
+    match picked.tool {
+        Tool::Add if picked.normal != (0, 0, 0) => {
+            let n = picked.normal;
+            writer.send(EditEvent {pos: (p.0 + n.0, p.1 + n.1, p.2 + n.2), filled: true});
+        },
+        Tool::Remove => writer.send(EditEvent {pos: p, filled: false}),
+        _ => {},
+    }
+}
+
+// Spawns or despawns just the voxels that changed
+pub fn edit_system(
+    mut commands: Commands,
+    handles: Res<HandleHolder>,
+    mut ui_state: ResMut<UiState>,
+    mut rot_con: ResMut<RotationConfig>,
+    mut voxels: ResMut<Voxels>,
+    mut events: EventReader<EditEvent>,
+    spawned: Query<(Entity, &Voxel)>
+) {
+    if events.is_empty() {
+        return;
+    }
+    let Voxels {grid, edits, ..} = voxels.as_mut();
+    let mut cleared = VoxelGrid::new();
+    for &EditEvent {pos: p, filled} in events.iter() {
+        if !edits.apply(grid, p, filled) {
+            continue;
+        }
+        if filled {
+            let (min, max) = &mut rot_con.bounds;
+            *min = (min.0.min(p.0), min.1.min(p.1), min.2.min(p.2));
+            *max = (max.0.max(p.0), max.1.max(p.1), max.2.max(p.2));
+            super::spawn_voxel(&mut commands, &handles, p);
+        }
+        else {
+            cleared.insert(p);
+        }
+    }
+    // one pass over the spawned voxels however many got cleared, like small components all at once
+    if !cleared.is_empty() {
+        for (e, v) in spawned.iter() {
+            if cleared.contains(v.0) {commands.entity(e).despawn();}
+        }
+    }
+    ui_state.voxel_count = grid.len();
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::Edits;
+    use crate::voxel_grid::VoxelGrid;
+
+    #[test]
+    fn edits_overlay_and_revert() {
+        let generated: VoxelGrid = [(0, 0, 0), (1, 0, 0)].into_iter().collect();
+        let mut grid = generated.clone();
+        let mut edits = Edits::default();
+
+        assert!(edits.apply(&mut grid, (2, 0, 0), true));
+        assert!(!edits.apply(&mut grid, (2, 0, 0), true));
+        assert!(edits.apply(&mut grid, (0, 0, 0), false));
+        assert_eq!(grid, [(1, 0, 0), (2, 0, 0)].into_iter().collect());
+        assert_eq!(edits.len(), 2);
+
+        // re-voxelizing gives the generated set again, the overlay puts the edits back
+        let mut fresh = generated.clone();
+        edits.overlay(&mut fresh);
+        assert_eq!(fresh, grid);
+
+        // putting a voxel back the way it was generated isn't an edit anymore
+        assert!(edits.apply(&mut grid, (0, 0, 0), true));
+        assert!(edits.apply(&mut grid, (2, 0, 0), false));
+        assert_eq!(edits.len(), 0);
+        assert_eq!(grid, generated);
+    }
+}
@@ -0,0 +1,100 @@
+use std::path::{Path, PathBuf};
+
+use bevy::prelude::*;
+
+use super::{ReloadVoxelsEvent, SchematicLoadedEvent, camera::PendingView, project::{self, ProjectEvent}};
+use crate::{schematic::Schematic, ui::UiState};
+
+const MAX_DIAGNOSTICS: usize = 5; // per file, the rest are summed up in one notification
+
+pub struct FilesPlugin;
+
+impl Plugin for FilesPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_event::<OpenFileEvent>()
+            .add_startup_system(cli_args_system)
+            .add_system(dropped_files_system)
+            .add_system(open_file_system.after(dropped_files_system));
+    }
+}
+
+// Loads a shape file and voxelizes it, from the load menu, the command line, drag and drop or the file watcher
+// Project files are passed on to be opened as projects
+pub struct OpenFileEvent(pub PathBuf);
+
+// A folder argument becomes the shapes folder, files are opened in order so the last one ends up loaded
+fn cli_args_system(mut ui_state: ResMut<UiState>, mut writer: EventWriter<OpenFileEvent>) {
+    for arg in std::env::args_os().skip(1) {
+        let path = PathBuf::from(arg);
+        if path.is_dir() {
+            ui_state.set_shapes_dir(path);
+        }
+        else {
+            writer.send(OpenFileEvent(path));
+        }
+    }
+}
+
+fn dropped_files_system(mut ui_state: ResMut<UiState>, mut dropped: EventReader<FileDragAndDrop>, mut writer: EventWriter<OpenFileEvent>) {
+    for event in dropped.iter() {
+        if let FileDragAndDrop::DroppedFile {path_buf, ..} = event {
+            if path_buf.is_dir() {
+                ui_state.set_shapes_dir(path_buf.clone());
+            }
+            else {
+                writer.send(OpenFileEvent(path_buf.clone()));
+            }
+        }
+    }
+}
+
+pub fn file_name(path: &Path) -> String {
+    path.file_name().map_or(path.display().to_string(), |n| n.to_string_lossy().into_owned())
+}
+
+// Files from outside the shapes folder are added to the load menu, a file that fails to load leaves the schematic as it was
+// Returns false if it failed, the error is shown and if it was the loaded file what's shown is marked stale
+pub fn load_schematic(schematic: &mut Schematic, ui_state: &mut UiState, path: &Path) -> bool {
+    match schematic.load(path) {
+        Ok(diagnostics) => {
+            ui_state.add_opened(path);
+            ui_state.current_file = Some(path.to_path_buf());
+            ui_state.stale = false;
+            ui_state.file_loaded(path);
+            for d in diagnostics.iter().take(MAX_DIAGNOSTICS) {
+                ui_state.notify_error(format!("{}: {}, skipped", path.display(), d), Some(path));
+            }
+            if diagnostics.len() > MAX_DIAGNOSTICS {
+                ui_state.notify_error(format!("{}: {} more invalid elements skipped", path.display(), diagnostics.len() - MAX_DIAGNOSTICS), Some(path));
+            }
+            true
+        },
+        Err(why) => {
+            ui_state.notify_error(why, Some(path));
+            if ui_state.current_file.as_deref() == Some(path) {ui_state.stale = true;}
+            false
+        },
+    }
+}
+
+pub fn open_file_system(
+    mut events: EventReader<OpenFileEvent>,
+    mut schematic: ResMut<Schematic>,
+    mut ui_state: ResMut<UiState>,
+    mut loaded: EventWriter<SchematicLoadedEvent>,
+    mut reloader: EventWriter<ReloadVoxelsEvent>,
+    mut projects: EventWriter<ProjectEvent>,
+    mut pending: ResMut<PendingView>
+) {
+    for OpenFileEvent(path) in events.iter() {
+        if project::is_project_file(path) {
+            projects.send(ProjectEvent::Open(path.clone()));
+        }
+        else if load_schematic(&mut schematic, &mut ui_state, path) {
+            pending.0 = None; // a project's camera doesn't fit this shape
+            loaded.send(SchematicLoadedEvent {name: file_name(path), edits: default()});
+            reloader.send(ReloadVoxelsEvent);
+        }
+    }
+}
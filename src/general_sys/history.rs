@@ -0,0 +1,229 @@
+use std::sync::Arc;
+
+use bevy::prelude::*;
+use bevy_egui::EguiContexts;
+
+use super::{AppState, RespawnVoxelsEvent, RotationConfig, SchematicLoadedEvent, Voxels, VoxelsReplacedEvent, edit::{self, EditEvent, Edits}};
+use crate::{consts, schematic::{Schematic, ShapeTransform, VoxelSources}, ui::UiState, voxel_grid::VoxelGrid};
+
+pub struct HistoryPlugin;
+
+impl Plugin for HistoryPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .insert_resource(History::default())
+            .add_event::<HistoryEvent>()
+            .add_system(record_system.after(super::finish_voxelize_system).after(edit::edit_system))
+            .add_system(history_keys_system.in_set(OnUpdate(AppState::Ui)))
+            .add_system(restore_system.after(record_system).after(history_keys_system));
+    }
+}
+
+pub enum HistoryEvent {
+    Undo,
+    Redo,
+    Goto(usize) // index into History::states
+}
+
+// Everything needed to go back to a voxelization or an edit without voxelizing again
+pub struct Snapshot {
+    pub label: String,
+    config: RotationConfig,
+    schematic: Arc<Schematic>, // shared between snapshots until another schematic gets loaded
+    grid: VoxelGrid,
+    transform: ShapeTransform,
+    edits: Edits,
+    sources: Option<Arc<VoxelSources>>
+}
+
+impl Snapshot {
+    pub fn voxel_count(&self) -> usize {
+        self.grid.len()
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct History {
+    states: Vec<Snapshot>, // oldest first, at most consts::HISTORY_LEN
+    current: usize,
+    loaded: Option<String> // schematic loaded since the last voxelization, named in the next snapshot's label
+}
+
+impl History {
+    pub fn states(&self) -> &[Snapshot] {
+        &self.states
+    }
+
+    pub fn current(&self) -> usize {
+        self.current
+    }
+
+    pub fn can_undo(&self) -> bool {
+        self.current > 0
+    }
+
+    pub fn can_redo(&self) -> bool {
+        self.current + 1 < self.states.len()
+    }
+
+    // Drops the states that could be redone, then the oldest ones if there are too many
+    fn push(&mut self, snapshot: Snapshot) {
+        self.states.truncate(self.current + 1);
+        self.states.push(snapshot);
+        if self.states.len() > consts::HISTORY_LEN {
+            self.states.remove(0);
+        }
+        self.current = self.states.len() - 1;
+    }
+}
+
+// A snapshot for every finished voxelization and every voxel edit
+fn record_system(
+    mut history: ResMut<History>,
+    voxels: Res<Voxels>,
+    rot_con: Res<RotationConfig>,
+    mut replaced: EventReader<VoxelsReplacedEvent>,
+    mut edited: EventReader<EditEvent>,
+    mut loaded: EventReader<SchematicLoadedEvent>
+) {
+    if let Some(SchematicLoadedEvent {name, ..}) = loaded.iter().last() {
+        history.loaded = Some(name.clone());
+    }
+
+    if !replaced.is_empty() {
+        replaced.clear();
+        edited.clear(); // edits made this frame went into the replaced voxels' overlay
+        let label = match history.loaded.take() {
+            Some(name) => format!("Loaded {}", name),
+            None => "Voxelized".to_string(),
+        };
+        history.push(Snapshot {
+            label,
+            config: rot_con.clone(),
+            schematic: voxels.schematic.clone(),
+            grid: voxels.grid.clone(),
+            transform: voxels.transform,
+            edits: voxels.edits.clone(),
+            sources: voxels.sources.clone()
+        });
+        return;
+    }
+
+    let count = edited.len();
+    let Some(EditEvent {pos: p, filled}) = edited.iter().last() else {return;};
+    let Some(prev) = history.states.get(history.current) else {return;};
+    if prev.edits == voxels.edits {
+        return; // the click didn't change anything
+    }
+    // the settings are the ones the voxels were made with, not whatever the menu shows now
+    let snapshot = Snapshot {
+        label: if count > 1 {format!("Edited {} voxels", count)}
+            else {format!("{} ({}, {}, {})", if *filled {"Added"} else {"Removed"}, p.0, p.1, p.2)},
+        config: prev.config.clone(),
+        schematic: prev.schematic.clone(),
+        grid: voxels.grid.clone(),
+        transform: voxels.transform,
+        edits: voxels.edits.clone(),
+        sources: voxels.sources.clone()
+    };
+    history.push(snapshot);
+}
+
+// Ctrl+Z undoes, Ctrl+Y or Ctrl+Shift+Z redoes
+fn history_keys_system(mut ctx: EguiContexts, keyboard: Res<Input<KeyCode>>, mut writer: EventWriter<HistoryEvent>) {
+    if ctx.ctx_mut().wants_keyboard_input() || !keyboard.any_pressed([KeyCode::LControl, KeyCode::RControl]) {
+        return;
+    }
+    let shift = keyboard.any_pressed([KeyCode::LShift, KeyCode::RShift]);
+    if keyboard.just_pressed(KeyCode::Z) {
+        writer.send(if shift {HistoryEvent::Redo} else {HistoryEvent::Undo});
+    }
+    if keyboard.just_pressed(KeyCode::Y) {
+        writer.send(HistoryEvent::Redo);
+    }
+}
+
+pub fn restore_system(
+    mut history: ResMut<History>,
+    mut events: EventReader<HistoryEvent>,
+    mut rot_con: ResMut<RotationConfig>,
+    mut schematic: ResMut<Schematic>,
+    mut voxels: ResMut<Voxels>,
+    mut ui_state: ResMut<UiState>,
+    mut respawn: EventWriter<RespawnVoxelsEvent>
+) {
+    if history.states.is_empty() {
+        events.clear();
+        return;
+    }
+    let last = history.states.len() - 1;
+    let mut target = history.current;
+    for event in events.iter() {
+        target = match *event {
+            HistoryEvent::Undo => target.saturating_sub(1),
+            HistoryEvent::Redo => (target + 1).min(last),
+            HistoryEvent::Goto(i) => i.min(last),
+        };
+    }
+    if target == history.current {
+        return;
+    }
+
+    let (prev, state) = (&history.states[history.current], &history.states[target]);
+    if !Arc::ptr_eq(&prev.schematic, &state.schematic) {
+        *schematic = (*state.schematic).clone();
+    }
+    ui_state.fit_clip(rot_con.bounds, state.config.bounds);
+    ui_state.voxel_count = state.grid.len();
+    *rot_con = state.config.clone();
+    voxels.grid = state.grid.clone();
+    voxels.schematic = state.schematic.clone();
+    voxels.transform = state.transform;
+    voxels.edits = state.edits.clone();
+    voxels.sources = state.sources.clone();
+    history.current = target;
+    respawn.send(RespawnVoxelsEvent);
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use super::{History, Snapshot};
+    use crate::consts;
+
+    fn snapshot(label: &str) -> Snapshot {
+        Snapshot {
+            label: label.to_string(),
+            config: Default::default(),
+            schematic: Arc::default(),
+            grid: Default::default(),
+            transform: Default::default(),
+            edits: Default::default(),
+            sources: None
+        }
+    }
+
+    #[test]
+    fn push_drops_redo_and_oldest() {
+        let mut h = History::default();
+        assert!(!h.can_undo() && !h.can_redo());
+        for l in ["a", "b", "c"] {
+            h.push(snapshot(l));
+        }
+        assert_eq!(h.current(), 2);
+        h.current = 0;
+        assert!(h.can_redo());
+        h.push(snapshot("d"));
+        let labels: Vec<_> = h.states().iter().map(|s| s.label.as_str()).collect();
+        assert_eq!(labels, vec!["a", "d"]);
+        assert!(!h.can_redo());
+
+        for i in 0..consts::HISTORY_LEN {
+            h.push(snapshot(&i.to_string()));
+        }
+        assert_eq!(h.states().len(), consts::HISTORY_LEN);
+        assert_eq!(h.states()[0].label, "0");
+        assert_eq!(h.current(), consts::HISTORY_LEN - 1);
+    }
+}
@@ -0,0 +1,181 @@
+use bevy::{prelude::*, render::{render_resource::PrimitiveTopology, view::NoFrustumCulling}};
+
+use super::{AppState, Voxels, picking::{self, Picked, Tool}};
+use crate::voxel_grid::Pos;
+
+pub struct MeasurePlugin;
+
+impl Plugin for MeasurePlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .insert_resource(Measure::default())
+            .add_startup_system(spawn_gizmo)
+            .add_system(measure_click_system.in_set(OnUpdate(AppState::Ui)).after(picking::pick_system))
+            .add_system(measure_gizmo_system.after(measure_click_system));
+    }
+}
+
+// Two voxels picked with the measure tool, the first click sets the start and the second the end
+#[derive(Resource, Default)]
+pub struct Measure {
+    pub start: Option<Pos>,
+    pub end: Option<Pos>,
+    pub filled: usize // voxels of the model on the line between them
+}
+
+impl Measure {
+    pub fn delta(&self) -> Option<Pos> {
+        let (a, b) = (self.start?, self.end?);
+        Some((b.0 - a.0, b.1 - a.1, b.2 - a.2))
+    }
+
+    pub fn manhattan(&self) -> Option<i32> {
+        let d = self.delta()?;
+        Some(d.0.abs() + d.1.abs() + d.2.abs())
+    }
+
+    // Between voxel centres
+    pub fn euclidean(&self) -> Option<f32> {
+        let d = self.delta()?;
+        Some(Vec3::new(d.0 as f32, d.1 as f32, d.2 as f32).length())
+    }
+
+    // Voxels on the line including both ends
+    pub fn line_len(&self) -> Option<usize> {
+        let d = self.delta()?;
+        Some(d.0.abs().max(d.1.abs()).max(d.2.abs()) as usize + 1)
+    }
+
+    pub fn clear(&mut self) {
+        self.start = None;
+        self.end = None;
+    }
+}
+
+// Voxels a straight line from a to b passes through, one per step along the longest axis, both ends included
+pub fn line(a: Pos, b: Pos) -> Vec<Pos> {
+    let d = (b.0 - a.0, b.1 - a.1, b.2 - a.2);
+    let n = d.0.abs().max(d.1.abs()).max(d.2.abs());
+    if n == 0 {
+        return vec![a];
+    }
+    // rounds d*i/n to the nearest integer, halves go up
+    let step = |d: i32, i: i32| (2*d*i + n).div_euclid(2*n);
+    (0..=n).map(|i| (a.0 + step(d.0, i), a.1 + step(d.1, i), a.2 + step(d.2, i))).collect()
+}
+
+#[derive(Component)]
+struct MeasureLine;
+
+#[derive(Component)]
+struct MeasureEnd(bool); // true for the end, false for the start
+
+fn spawn_gizmo(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>
+) {
+    let cube = meshes.add(Mesh::from(shape::Cube { size: 1.06 }));
+    let marker = materials.add(StandardMaterial {
+        base_color: Color::rgba(1.0, 1.0, 0.0, 0.5),
+        alpha_mode: AlphaMode::Blend,
+        unlit: true,
+        ..default()
+    });
+    for end in [false, true] {
+        commands.spawn((PbrBundle {
+            mesh: cube.clone(),
+            material: marker.clone(),
+            visibility: Visibility::Hidden,
+            ..default()
+        }, MeasureEnd(end)));
+    }
+
+    let mut mesh = Mesh::new(PrimitiveTopology::LineList);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, vec![[0., 0., 0.]; 2]);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, vec![[0., 1., 0.]; 2]);
+    commands.spawn((PbrBundle {
+        mesh: meshes.add(mesh),
+        material: materials.add(StandardMaterial {
+            base_color: Color::YELLOW,
+            unlit: true,
+            ..default()
+        }),
+        visibility: Visibility::Hidden,
+        ..default()
+    }, MeasureLine, NoFrustumCulling)); // the bounding box is computed once, before the line is moved anywhere
+}
+
+fn measure_click_system(picked: Res<Picked>, mut measure: ResMut<Measure>) {
+    if picked.tool != Tool::Measure || !picked.clicked {
+        return;
+    }
+    let Some(p) = picked.hovered else {return;};
+    if measure.start.is_none() || measure.end.is_some() {
+        measure.start = Some(p);
+        measure.end = None;
+    }
+    else {
+        measure.end = Some(p);
+    }
+}
+
+// Moves the markers and the line between the two voxels' centres and counts the model's voxels along it
+fn measure_gizmo_system(
+    mut measure: ResMut<Measure>,
+    voxels: Res<Voxels>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut ends: Query<(&mut Transform, &mut Visibility, &MeasureEnd), Without<MeasureLine>>,
+    mut lines: Query<(&Handle<Mesh>, &mut Visibility), With<MeasureLine>>
+) {
+    if !measure.is_changed() && !voxels.is_changed() {
+        return;
+    }
+    for (mut tf, mut vis, end) in ends.iter_mut() {
+        match if end.0 {measure.end} else {measure.start} {
+            Some(p) => {
+                tf.translation = Vec3::new(p.0 as f32, p.1 as f32, p.2 as f32);
+                *vis = Visibility::Visible;
+            },
+            None => *vis = Visibility::Hidden,
+        }
+    }
+
+    let Ok((handle, mut vis)) = lines.get_single_mut() else {return;};
+    let (Some(a), Some(b)) = (measure.start, measure.end) else {
+        *vis = Visibility::Hidden;
+        return;
+    };
+
+    // written without change detection, otherwise this would run again next frame
+    measure.bypass_change_detection().filled = line(a, b).into_iter().filter(|p| voxels.grid.contains(*p)).count();
+    if let Some(mesh) = meshes.get_mut(handle) {
+        let to_array = |p: Pos| [p.0 as f32, p.1 as f32, p.2 as f32];
+        mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, vec![to_array(a), to_array(b)]);
+    }
+    *vis = Visibility::Visible;
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::line;
+
+    #[test]
+    fn line_steps_along_longest_axis() {
+        assert_eq!(line((2, 3, 4), (2, 3, 4)), vec![(2, 3, 4)]);
+        assert_eq!(line((0, 0, 0), (3, 1, 0)), vec![(0, 0, 0), (1, 0, 0), (2, 1, 0), (3, 1, 0)]);
+        assert_eq!(line((0, 0, 0), (0, -4, 0)).len(), 5);
+
+        let diagonal = line((1, 1, 1), (-2, -2, -2));
+        assert_eq!(diagonal, vec![(1, 1, 1), (0, 0, 0), (-1, -1, -1), (-2, -2, -2)]);
+
+        // both ends are always included whichever way it's drawn
+        for (a, b, len) in [((0, 0, 0), (7, -3, 2), 8), ((-5, 2, 9), (1, 1, -4), 14)] {
+            for l in [line(a, b), line(b, a)] {
+                assert!(l.contains(&a) && l.contains(&b));
+                assert_eq!(l.len(), len);
+            }
+        }
+    }
+}
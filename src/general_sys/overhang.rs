@@ -0,0 +1,168 @@
+use std::path::PathBuf;
+
+use bevy::prelude::*;
+
+use super::{HandleHolder, Voxels, colouring};
+use crate::{schematic::Schematic, ui::UiState, voxel_grid::{Pos, VoxelGrid}};
+
+pub struct OverhangPlugin;
+
+impl Plugin for OverhangPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_event::<ExportScaffoldingEvent>()
+            .insert_resource(Overhangs::default())
+            .insert_resource(Scaffolding::default())
+            // after everything that changes the voxels in Update, so they're painted with the new ones
+            .add_system(overhang_system.in_base_set(CoreSet::PostUpdate).before(colouring::paint_voxels_system))
+            .add_system(spawn_scaffolding_system.in_base_set(CoreSet::PostUpdate).after(overhang_system))
+            .add_system(export_scaffolding_system);
+    }
+}
+
+// What counts as holding up a voxel, the voxels in the layer below that are checked
+#[derive(PartialEq, Eq, Debug, Default, Clone, Copy)]
+pub enum Support {
+    Below, // straight below only
+    #[default]
+    Faces, // below or one step along x or z, like a 45 degree overhang
+    Diagonal // any of the 9 voxels below
+}
+
+impl Support {
+    pub const ALL: [Support; 3] = [Self::Below, Self::Faces, Self::Diagonal];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Below => "Straight below",
+            Self::Faces => "Below and sideways",
+            Self::Diagonal => "Below, sideways and diagonal",
+        }
+    }
+
+    // x and z offsets in the layer below
+    fn offsets(self) -> &'static [(i32, i32)] {
+        match self {
+            Self::Below => &[(0, 0)],
+            Self::Faces => &[(0, 0), (1, 0), (-1, 0), (0, 1), (0, -1)],
+            Self::Diagonal => &[(0, 0), (1, 0), (-1, 0), (0, 1), (0, -1), (1, 1), (1, -1), (-1, 1), (-1, -1)],
+        }
+    }
+}
+
+// Voxels with nothing holding them up, the lowest layer stands on the ground
+#[derive(Resource, Default)]
+pub struct Overhangs(pub VoxelGrid);
+
+pub fn unsupported(grid: &VoxelGrid, support: Support) -> VoxelGrid {
+    let Some((ground, _)) = grid.yrange() else {return VoxelGrid::new();};
+    grid.iter()
+        .filter(|p| p.1 > ground && !support.offsets().iter().any(|(dx, dz)| grid.contains((p.0 + dx, p.1 - 1, p.2 + dz))))
+        .collect()
+}
+
+// Pillars from under each unsupported voxel down to the model or the ground, a layer of its own
+#[derive(Resource, Default)]
+pub struct Scaffolding(pub VoxelGrid);
+
+#[derive(Component)]
+pub struct ScaffoldVoxel(pub Pos);
+
+// Saves the scaffolding as a schematic of points
+pub struct ExportScaffoldingEvent(pub PathBuf);
+
+pub fn scaffolding(grid: &VoxelGrid, unsupported: &VoxelGrid) -> VoxelGrid {
+    let Some((ground, _)) = grid.yrange() else {return VoxelGrid::new();};
+    let mut ret = VoxelGrid::new();
+    for (x, y, z) in unsupported.iter() {
+        for y in (ground..y).rev().take_while(|&y| !grid.contains((x, y, z))) {
+            ret.insert((x, y, z));
+        }
+    }
+    ret
+}
+
+fn overhang_system(
+    voxels: Res<Voxels>,
+    mut ui_state: ResMut<UiState>,
+    mut overhangs: ResMut<Overhangs>,
+    mut scaffold: ResMut<Scaffolding>,
+    mut computed: Local<Option<Support>>
+) {
+    let support = ui_state.support;
+    if !voxels.is_changed() && *computed == Some(support) {
+        return;
+    }
+    *computed = Some(support);
+    overhangs.0 = unsupported(&voxels.grid, support);
+    scaffold.0 = scaffolding(&voxels.grid, &overhangs.0);
+    ui_state.overhang_count = overhangs.0.len();
+    ui_state.scaffold_count = scaffold.0.len();
+}
+
+// Kept apart from the voxels, so picking, editing and history leave it alone
+fn spawn_scaffolding_system(
+    mut commands: Commands,
+    handles: Res<HandleHolder>,
+    ui_state: Res<UiState>,
+    scaffold: Res<Scaffolding>,
+    previous: Query<Entity, With<ScaffoldVoxel>>,
+    mut shown: Local<bool>
+) {
+    if !scaffold.is_changed() && *shown == ui_state.show_scaffolding {
+        return;
+    }
+    *shown = ui_state.show_scaffolding;
+    for e in previous.iter() {
+        commands.entity(e).despawn();
+    }
+    if !*shown {
+        return;
+    }
+    for p in scaffold.0.iter() {
+        commands.spawn((super::voxel_bundle(&handles, p), ScaffoldVoxel(p)));
+    }
+}
+
+fn export_scaffolding_system(mut events: EventReader<ExportScaffoldingEvent>, scaffold: Res<Scaffolding>, mut ui_state: ResMut<UiState>) {
+    for ExportScaffoldingEvent(path) in events.iter() {
+        match Schematic::from_voxels(&scaffold.0).save_to_file(path) {
+            Ok(()) => ui_state.saved(path.clone()),
+            Err(why) => ui_state.notify_error(why, None),
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::voxel_grid::VoxelGrid;
+    use super::{scaffolding, unsupported, Support};
+
+    #[test]
+    fn support_tolerances() {
+        // a column with a step sideways, a step diagonally and a floating voxel
+        let grid: VoxelGrid = [(0, 0, 0), (0, 1, 0), (1, 2, 0), (2, 3, 1), (5, 5, 5)].into_iter().collect();
+        let found = |support| {
+            let mut v: Vec<_> = unsupported(&grid, support).iter().collect();
+            v.sort();
+            v
+        };
+        assert_eq!(found(Support::Below), vec![(1, 2, 0), (2, 3, 1), (5, 5, 5)]);
+        assert_eq!(found(Support::Faces), vec![(2, 3, 1), (5, 5, 5)]);
+        assert_eq!(found(Support::Diagonal), vec![(5, 5, 5)]);
+        assert!(unsupported(&VoxelGrid::new(), Support::Below).is_empty());
+    }
+
+    #[test]
+    fn pillars_stop_at_the_model_or_ground() {
+        // a floor, a ledge sticking out over it and a beam out past the floor's edge
+        let mut grid: VoxelGrid = (0..4).map(|x| (x, 0, 0)).collect();
+        grid.extend([(0, 3, 0), (1, 3, 0), (6, 2, 0)]);
+        let holes = unsupported(&grid, Support::Below);
+        let mut pillars: Vec<_> = scaffolding(&grid, &holes).iter().collect();
+        pillars.sort();
+        assert_eq!(pillars, vec![(0, 1, 0), (0, 2, 0), (1, 1, 0), (1, 2, 0), (6, 0, 0), (6, 1, 0)]);
+        assert!(unsupported(&grid.iter().chain(pillars).collect(), Support::Below).is_empty());
+    }
+}
@@ -0,0 +1,124 @@
+use std::sync::Arc;
+
+use bevy::{prelude::*, render::{mesh::Indices, render_resource::PrimitiveTopology}};
+
+use super::Voxels;
+use crate::{schematic::{Schematic, ShapeTransform}, ui::UiState};
+
+pub struct OverlayPlugin;
+
+impl Plugin for OverlayPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .insert_resource(OverlayState::default())
+            .add_startup_system(init_overlay_materials)
+            .add_system(overlay_system);
+    }
+}
+
+// How the source schematic is drawn over the voxels
+#[derive(PartialEq, Eq, Debug, Default, Clone, Copy)]
+pub enum OverlayMode {
+    #[default]
+    Off,
+    Wireframe,
+    Translucent
+}
+
+#[derive(Component)]
+pub struct Overlay;
+
+#[derive(Resource, Default)]
+struct OverlayState {
+    wireframe: Handle<StandardMaterial>,
+    translucent: Handle<StandardMaterial>,
+    shown: OverlayMode, // mode of the currently spawned overlay
+    source: Option<(Arc<Schematic>, ShapeTransform)> // what it was built from
+}
+
+fn init_overlay_materials(mut materials: ResMut<Assets<StandardMaterial>>, mut handles: ResMut<OverlayState>) {
+    handles.wireframe = materials.add(StandardMaterial {
+        base_color: Color::rgb(0.1, 0.1, 0.1),
+        unlit: true,
+        ..default()
+    });
+    handles.translucent = materials.add(StandardMaterial {
+        base_color: Color::rgba(0.2, 0.6, 1.0, 0.35),
+        alpha_mode: AlphaMode::Blend,
+        double_sided: true,
+        cull_mode: None,
+        ..default()
+    });
+}
+
+// Flat shaded triangles, vertices aren't shared so every triangle gets its own normal
+fn translucent_mesh(tris: &[[Vec3; 3]]) -> Mesh {
+    let mut positions = Vec::with_capacity(tris.len()*3);
+    let mut normals = Vec::with_capacity(tris.len()*3);
+    for [a, b, c] in tris {
+        let n = (*b - *a).cross(*c - *a).normalize_or_zero();
+        for p in [a, b, c] {
+            positions.push(p.to_array());
+            normals.push(n.to_array());
+        }
+    }
+    let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, normals);
+    mesh
+}
+
+// Each triangle's three edges as a line list, edges shared between triangles are drawn twice
+fn wireframe_mesh(tris: &[[Vec3; 3]]) -> Mesh {
+    let mut positions = Vec::with_capacity(tris.len()*3);
+    let mut indices = Vec::with_capacity(tris.len()*6);
+    for tri in tris {
+        let first = positions.len() as u32;
+        positions.extend(tri.iter().map(|p| p.to_array()));
+        indices.extend([0, 1, 1, 2, 2, 0].map(|i| first + i));
+    }
+    let normals = vec![[0., 1., 0.]; positions.len()]; // unlit, but the pbr pipeline still wants normals
+    let mut mesh = Mesh::new(PrimitiveTopology::LineList);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, normals);
+    mesh.set_indices(Some(Indices::U32(indices)));
+    mesh
+}
+
+// Rebuilds the overlay when the voxels are replaced or the mode changes, edits to the voxels leave it as it is
+// Uses the schematic and transform the current voxels were made with, so the two always line up
+fn overlay_system(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut state: ResMut<OverlayState>,
+    ui_state: Res<UiState>,
+    voxels: Res<Voxels>,
+    previous: Query<Entity, With<Overlay>>
+) {
+    let mode = ui_state.overlay;
+    let same_source = state.source.as_ref().is_some_and(|(s, tf)| Arc::ptr_eq(s, &voxels.schematic) && *tf == voxels.transform);
+    if mode == state.shown && same_source {
+        return;
+    }
+    state.shown = mode;
+    state.source = Some((voxels.schematic.clone(), voxels.transform));
+
+    for e in previous.iter() {
+        commands.entity(e).despawn();
+    }
+
+    let tris = || voxels.schematic.transformed_triangles(&voxels.transform);
+    let (mesh, material) = match mode {
+        OverlayMode::Off => return,
+        OverlayMode::Wireframe => (wireframe_mesh(&tris()), state.wireframe.clone()),
+        OverlayMode::Translucent => (translucent_mesh(&tris()), state.translucent.clone()),
+    };
+    commands.spawn((
+        PbrBundle {
+            mesh: meshes.add(mesh),
+            material,
+            ..default()
+        },
+        Overlay
+    ));
+}
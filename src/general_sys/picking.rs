@@ -0,0 +1,217 @@
+use bevy::{prelude::*, ecs::system::SystemParam, window::PrimaryWindow};
+use bevy_egui::EguiContexts;
+
+use super::{AppState, RotationConfig, Voxels};
+use crate::{ui::{ClipState, UiState}, voxel_grid::{Pos, VoxelGrid}};
+
+pub struct PickingPlugin;
+
+impl Plugin for PickingPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .insert_resource(Picked::default())
+            .add_startup_system(spawn_markers)
+            .add_system(pick_system.in_set(OnUpdate(AppState::Ui)))
+            .add_system(marker_system.after(pick_system));
+    }
+}
+
+const CLICK_SLOP: f32 = 4.; // pixels the cursor can move between press and release for it to still count as a click
+
+// What left clicking a voxel does
+#[derive(PartialEq, Eq, Debug, Default, Clone, Copy)]
+pub enum Tool {
+    #[default]
+    Inspect,
+    Measure,
+    Add,
+    Remove
+}
+
+// Voxel under the cursor and the origin chosen for relative coordinates
+#[derive(Resource, Default)]
+pub struct Picked {
+    pub hovered: Option<Pos>,
+    pub normal: Pos, // face of the hovered voxel the ray entered through, zero if the ray started inside it
+    pub origin: Option<Pos>,
+    pub tool: Tool,
+    pub clicked: bool, // the hovered voxel was left clicked this frame, dragging to orbit doesn't count
+    press: Option<Vec2> // cursor position when the left button went down
+}
+
+impl Picked {
+    pub fn relative(&self) -> Option<Pos> {
+        let (h, o) = (self.hovered?, self.origin?);
+        Some((h.0 - o.0, h.1 - o.1, h.2 - o.2))
+    }
+}
+
+#[derive(Component, PartialEq, Eq)]
+enum Marker {
+    Hover,
+    Origin
+}
+
+// Ray from the camera through the cursor
+#[derive(SystemParam)]
+pub struct CursorRay<'w, 's> {
+    windows: Query<'w, 's, &'static Window, With<PrimaryWindow>>,
+    cameras: Query<'w, 's, (&'static Camera, &'static GlobalTransform)>
+}
+
+impl CursorRay<'_, '_> {
+    pub fn position(&self) -> Option<Vec2> {
+        self.windows.get_single().ok()?.cursor_position()
+    }
+
+    pub fn get(&self) -> Option<Ray> {
+        let cursor = self.position()?;
+        let (camera, cam_tf) = self.cameras.get_single().ok()?;
+        camera.viewport_to_world(cam_tf, cursor)
+    }
+}
+
+fn spawn_markers(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>
+) {
+    let cube = meshes.add(Mesh::from(shape::Cube { size: 1.06 })); // slightly larger so it isn't hidden inside the voxel
+    for (color, marker) in [(Color::rgba(1.0, 1.0, 1.0, 0.5), Marker::Hover), (Color::rgba(1.0, 0.0, 1.0, 0.5), Marker::Origin)] {
+        commands.spawn((PbrBundle {
+            mesh: cube.clone(),
+            material: materials.add(StandardMaterial {
+                base_color: color,
+                alpha_mode: AlphaMode::Blend,
+                unlit: true,
+                ..default()
+            }),
+            visibility: Visibility::Hidden,
+            ..default()
+        }, marker));
+    }
+}
+
+// Steps through the voxels along the ray (3D DDA) within the inclusive bounds, returns the first voxel accepted by `hit`
+// and the normal of the face it was entered through
+pub fn raycast(bounds: (Pos, Pos), origin: Vec3, dir: Vec3, mut hit: impl FnMut(Pos) -> bool) -> Option<(Pos, Pos)> {
+    let (min, max) = (IVec3::new(bounds.0.0, bounds.0.1, bounds.0.2), IVec3::new(bounds.1.0, bounds.1.1, bounds.1.2));
+    let (lo, hi) = (min.as_vec3() - 0.5, max.as_vec3() + 0.5);
+    let inv = dir.recip();
+    let (t1, t2) = ((lo - origin)*inv, (hi - origin)*inv);
+    let (tnear, tfar) = (t1.min(t2), t1.max(t2));
+    let (tenter, texit) = (tnear.max_element(), tfar.min_element());
+    if texit < 0. || tenter > texit {
+        return None;
+    }
+
+    let step = IVec3::from_array(dir.to_array().map(|d| if d > 0. {1} else if d < 0. {-1} else {0}));
+    let mut normal = IVec3::ZERO;
+    let mut t = 0.;
+    if tenter > 0. {
+        t = tenter;
+        let axis = if tnear.x >= tnear.y && tnear.x >= tnear.z {0} else if tnear.y >= tnear.z {1} else {2};
+        normal[axis] = -step[axis];
+    }
+    let start = origin + dir*t;
+    let mut cell = (start + 0.5).floor().as_ivec3();
+    // the entry point is on the box's face, make sure rounding didn't put it in the cell in front of it
+    if let Some(axis) = (0..3).find(|a| normal[*a] != 0) {
+        cell[axis] = if step[axis] > 0 {min[axis]} else {max[axis]};
+    }
+
+    let delta = inv.abs();
+    let mut tmax = Vec3::ZERO;
+    for a in 0..3 {
+        tmax[a] = if step[a] == 0 {f32::INFINITY}
+        else {((cell[a] as f32 + 0.5*step[a] as f32) - origin[a]) * inv[a]};
+    }
+
+    while cell.cmpge(min).all() && cell.cmple(max).all() {
+        let p = (cell.x, cell.y, cell.z);
+        if hit(p) {
+            return Some((p, (normal.x, normal.y, normal.z)));
+        }
+        let axis = if tmax.x <= tmax.y && tmax.x <= tmax.z {0} else if tmax.y <= tmax.z {1} else {2};
+        cell[axis] += step[axis];
+        tmax[axis] += delta[axis];
+        normal = IVec3::ZERO;
+        normal[axis] = -step[axis];
+    }
+    None
+}
+
+// Only voxels that are rendered can be picked
+pub fn visible(grid: &VoxelGrid, ui_state: &UiState, p: Pos) -> bool {
+    grid.contains(p) && !matches!(ui_state.clip_state(p), ClipState::Hidden)
+}
+
+// Right click sets the hovered voxel as the origin for relative coordinates, left clicks are left to the current tool
+pub fn pick_system(
+    mut ctx: EguiContexts,
+    cursor: CursorRay,
+    mouse: Res<Input<MouseButton>>,
+    voxels: Res<Voxels>,
+    rot_con: Res<RotationConfig>,
+    ui_state: Res<UiState>,
+    mut picked: ResMut<Picked>
+) {
+    picked.hovered = None;
+    picked.clicked = false;
+    if ctx.ctx_mut().is_pointer_over_area() {
+        picked.press = None;
+        return;
+    }
+    let Some(ray) = cursor.get() else {return;};
+    if mouse.just_pressed(MouseButton::Left) {
+        picked.press = cursor.position();
+    }
+    let click = mouse.just_released(MouseButton::Left) && match (picked.press.take(), cursor.position()) {
+        (Some(a), Some(b)) => a.distance(b) <= CLICK_SLOP,
+        _ => false,
+    };
+
+    if let Some((p, normal)) = raycast(rot_con.bounds, ray.origin, ray.direction, |p| visible(&voxels.grid, &ui_state, p)) {
+        picked.hovered = Some(p);
+        picked.normal = normal;
+        picked.clicked = click;
+        if mouse.just_pressed(MouseButton::Right) {
+            picked.origin = Some(p);
+        }
+    }
+}
+
+fn marker_system(picked: Res<Picked>, mut markers: Query<(&mut Transform, &mut Visibility, &Marker)>) {
+    for (mut tf, mut vis, marker) in markers.iter_mut() {
+        let p = if *marker == Marker::Hover {picked.hovered} else {picked.origin};
+        match p {
+            Some(p) => {
+                tf.translation = Vec3::new(p.0 as f32, p.1 as f32, p.2 as f32);
+                *vis = Visibility::Visible;
+            },
+            None => *vis = Visibility::Hidden,
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use bevy::prelude::Vec3;
+    use super::raycast;
+    use crate::voxel_grid::VoxelGrid;
+
+    #[test]
+    fn raycast_hits_first_voxel() {
+        let grid: VoxelGrid = [(0, 0, 0), (3, 0, 0), (3, 2, 1)].into_iter().collect();
+        let bounds = ((0, 0, 0), (3, 2, 1));
+        let hit = |o: Vec3, d: Vec3| raycast(bounds, o, d.normalize(), |p| grid.contains(p));
+
+        assert_eq!(hit(Vec3::new(-5., 0.1, 0.), Vec3::X), Some(((0, 0, 0), (-1, 0, 0))));
+        assert_eq!(hit(Vec3::new(10., 0., 0.2), -Vec3::X), Some(((3, 0, 0), (1, 0, 0))));
+        assert_eq!(hit(Vec3::new(3., 10., 1.), -Vec3::Y), Some(((3, 2, 1), (0, 1, 0))));
+        assert_eq!(hit(Vec3::new(1., 0., 0.), Vec3::X), Some(((3, 0, 0), (-1, 0, 0))));
+        assert_eq!(hit(Vec3::new(-5., 5., 0.), Vec3::X), None);
+        assert_eq!(hit(Vec3::new(0., 5., 0.), Vec3::new(0.05, -1., 0.)), Some(((0, 0, 0), (0, 1, 0))));
+    }
+}
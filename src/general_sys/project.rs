@@ -0,0 +1,184 @@
+use std::path::{Path, PathBuf};
+
+use bevy::prelude::*;
+
+use super::{CentreMode, ReloadVoxelsEvent, RotationConfig, SchematicLoadedEvent, Voxels, camera::{CameraMode, CameraView, CamRotation, OrbitState, PendingView}, edit::Edits, files};
+use crate::{ron_file, schematic::Schematic, ui::{AxisClip, UiState}, voxel_grid::Pos};
+
+pub const EXTENSION: &str = "ultvox";
+
+pub struct ProjectPlugin;
+
+impl Plugin for ProjectPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_event::<ProjectEvent>()
+            .add_system(save_project_system)
+            .add_system(open_project_system.after(files::open_file_system).before(super::reload_voxel_system));
+    }
+}
+
+pub enum ProjectEvent {
+    Save(PathBuf),
+    Open(PathBuf)
+}
+
+pub fn is_project_file(path: &Path) -> bool {
+    path.extension().is_some_and(|e| e == EXTENSION)
+}
+
+// Everything needed to get the same voxels and view back from the source model, saved as RON
+#[derive(serde::Serialize, serde::Deserialize, Debug, PartialEq)]
+struct Project {
+    model: PathBuf, // relative to the project file if it's in the same folder or below
+    rotation: (f32, f32, f32), // yaw, pitch, roll in radians
+    scale: Vec3,
+    mirror: (bool, bool, bool),
+    offset: Vec3,
+    centre: CentreMode,
+    bounds: (Pos, Pos), // of the voxels when saved, so the clip settings are kept as they were
+    clip: [AxisClip; 3],
+    added: Vec<Pos>,
+    removed: Vec<Pos>,
+    camera: CameraView
+}
+
+// Paths outside the project's folder are made absolute, so moving the project along with its model keeps it working
+fn model_reference(project: &Path, model: &Path) -> PathBuf {
+    let model = std::fs::canonicalize(model).unwrap_or_else(|_| model.to_path_buf());
+    let dir = project.parent().and_then(|d| std::fs::canonicalize(d).ok());
+    match dir.and_then(|d| model.strip_prefix(d).ok().map(Path::to_path_buf)) {
+        Some(relative) => relative,
+        None => model,
+    }
+}
+
+fn save_project_system(
+    mut events: EventReader<ProjectEvent>,
+    rot_con: Res<RotationConfig>,
+    voxels: Res<Voxels>,
+    mut ui_state: ResMut<UiState>,
+    cam_mode: Res<CameraMode>,
+    camera: Query<(&Transform, &CamRotation, &OrbitState, &Projection)>
+) {
+    for event in events.iter() {
+        let ProjectEvent::Save(path) = event else {continue;};
+        let Some(model) = &ui_state.current_file else {
+            ui_state.notify_error("load a shape before saving a project", None);
+            continue;
+        };
+        // the folder has to exist for the model path to be made relative to it
+        if let Some(Err(why)) = path.parent().map(std::fs::create_dir_all) {
+            ui_state.notify_error(format!("couldn't create the folder for {}: {}", path.display(), why), None);
+            continue;
+        }
+        let (tf, rot, orbit, projection) = camera.single();
+        let project = Project {
+            model: model_reference(path, model),
+            rotation: (rot_con.rotx, rot_con.roty, rot_con.rotz),
+            scale: rot_con.scale,
+            mirror: rot_con.mirror,
+            offset: rot_con.offset,
+            centre: rot_con.centre,
+            bounds: rot_con.bounds,
+            clip: ui_state.clip_settings(),
+            added: voxels.edits.added().collect(),
+            removed: voxels.edits.removed().collect(),
+            camera: CameraView::capture(*cam_mode, tf, rot, orbit, projection)
+        };
+        match ron_file::write_ron(&project, path) {
+            Ok(()) => ui_state.saved(path.clone()),
+            Err(why) => ui_state.notify_error(why, None),
+        }
+    }
+}
+
+// Loads the model, then voxelizes it with the saved settings and puts the edits and the camera back once that's done
+fn open_project_system(
+    mut events: EventReader<ProjectEvent>,
+    mut schematic: ResMut<Schematic>,
+    mut rot_con: ResMut<RotationConfig>,
+    mut ui_state: ResMut<UiState>,
+    mut pending: ResMut<PendingView>,
+    mut loaded: EventWriter<SchematicLoadedEvent>,
+    mut reloader: EventWriter<ReloadVoxelsEvent>
+) {
+    for event in events.iter() {
+        let ProjectEvent::Open(path) = event else {continue;};
+        let project: Project = match ron_file::read_ron(path) {
+            Ok(project) => project,
+            Err(why) => {
+                ui_state.notify_error(why, Some(path));
+                continue;
+            },
+        };
+        let model = path.parent().unwrap_or(Path::new("")).join(&project.model);
+        if !files::load_schematic(&mut schematic, &mut ui_state, &model) {
+            continue;
+        }
+        ui_state.add_opened(path);
+        ui_state.file_loaded(path);
+
+        (rot_con.rotx, rot_con.roty, rot_con.rotz) = project.rotation;
+        rot_con.scale = project.scale;
+        rot_con.mirror = project.mirror;
+        rot_con.offset = project.offset;
+        rot_con.centre = project.centre;
+        rot_con.bounds = project.bounds;
+        ui_state.set_clip(project.clip);
+        pending.0 = Some(project.camera);
+        loaded.send(SchematicLoadedEvent {name: files::file_name(path), edits: Edits::from_voxels(project.added, project.removed)});
+        reloader.send(ReloadVoxelsEvent);
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+    use super::{model_reference, Project};
+
+    #[test]
+    fn model_paths_relative_to_project() {
+        let dir = std::env::temp_dir().join(format!("ultvox_project_test_{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("models")).unwrap();
+        std::fs::write(dir.join("models/a.obj"), "").unwrap();
+
+        assert_eq!(model_reference(&dir.join("p.ultvox"), &dir.join("models/a.obj")), PathBuf::from("models/a.obj"));
+        assert_eq!(model_reference(&dir.join("models/p.ultvox"), &dir.join("models/../models/a.obj")), PathBuf::from("a.obj"));
+        // outside the project's folder
+        let outside = model_reference(&dir.join("models/sub/p.ultvox"), &dir.join("models/a.obj"));
+        assert!(outside.is_absolute());
+        assert_eq!(outside, std::fs::canonicalize(dir.join("models/a.obj")).unwrap());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn project_round_trip() {
+        let text = r#"(
+            model: "models/a.obj",
+            rotation: (0.5, 0.0, -1.0),
+            scale: (1.0, 2.0, 1.0),
+            mirror: (true, false, false),
+            offset: (0.5, 0.0, 0.0),
+            centre: VoxelCentre,
+            bounds: ((-3, 0, -3), (3, 9, 3)),
+            clip: ((min: -3, max: 3, preview: 0), (min: 0, max: 4, preview: 2), (min: -3, max: 3, preview: 0)),
+            added: [(0, 10, 0)],
+            removed: [(1, 1, 1), (2, 2, 2)],
+            camera: (
+                mode: Orbit,
+                translation: (0.0, 5.0, 20.0),
+                rotation: (0.0, 0.0, 0.0, 1.0),
+                fly: (0.0, 0.0),
+                orbit: ((0.0, 4.5, 0.0), 15.0, 0.0, 0.2),
+                ortho: Some((12.0, 1.0)),
+            ),
+        )"#;
+        let project: Project = ron::from_str(text).unwrap();
+        assert_eq!(project.removed.len(), 2);
+        let again: Project = ron::from_str(&ron::to_string(&project).unwrap()).unwrap();
+        assert_eq!(project, again);
+    }
+}
@@ -0,0 +1,80 @@
+use std::{collections::HashMap, fs::read_dir, path::{Path, PathBuf}, time::SystemTime};
+
+use bevy::prelude::*;
+
+use super::files::OpenFileEvent;
+use crate::ui::{self, UiState};
+
+const POLL_SECONDS: f32 = 0.5;
+
+pub struct WatchPlugin;
+
+impl Plugin for WatchPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .insert_resource(ShapeWatcher::default())
+            .add_system(watch_shapes_system);
+    }
+}
+
+type FileStamp = (SystemTime, u64); // modified time and size, some file systems only store whole seconds
+
+// Polls the shapes folder for files being added or removed and the loaded file for changes
+#[derive(Resource)]
+struct ShapeWatcher {
+    timer: Timer,
+    files: HashMap<PathBuf, FileStamp>,
+    current: Option<(PathBuf, FileStamp)> // can be outside the shapes folder
+}
+
+impl Default for ShapeWatcher {
+    fn default() -> Self {
+        Self {timer: Timer::from_seconds(POLL_SECONDS, TimerMode::Repeating), files: HashMap::new(), current: None}
+    }
+}
+
+fn stamp(path: &Path) -> Option<FileStamp> {
+    let meta = std::fs::metadata(path).ok()?;
+    Some((meta.modified().ok()?, meta.len()))
+}
+
+// Shape files in the folder and its subfolders
+fn scan(dir: &Path, files: &mut HashMap<PathBuf, FileStamp>) {
+    let Ok(entries) = read_dir(dir) else {return;};
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if entry.file_type().is_ok_and(|t| t.is_dir()) {
+            scan(&path, files);
+        }
+        else if ui::is_loadable_file(&path) {
+            if let Some(s) = stamp(&path) {files.insert(path, s);}
+        }
+    }
+}
+
+// Refreshes the file list when files come or go, opens the loaded file again when it changes on disk
+fn watch_shapes_system(
+    time: Res<Time>,
+    mut watcher: ResMut<ShapeWatcher>,
+    mut ui_state: ResMut<UiState>,
+    mut opener: EventWriter<OpenFileEvent>
+) {
+    if !watcher.timer.tick(time.delta()).just_finished() {
+        return;
+    }
+    let mut files = HashMap::new();
+    scan(&ui_state.shapes_dir, &mut files);
+    if files.len() != watcher.files.len() || files.keys().any(|path| !watcher.files.contains_key(path)) {
+        ui_state.reload_files();
+    }
+    watcher.files = files;
+
+    let Some(path) = &ui_state.current_file else {return;};
+    // editors can briefly remove the file while saving, the old stamp is kept until it's back
+    let Some(new) = stamp(path) else {return;};
+    match &watcher.current {
+        Some((p, old)) if p == path && *old != new => opener.send(OpenFileEvent(path.clone())),
+        _ => {},
+    }
+    watcher.current = Some((path.clone(), new));
+}
@@ -3,20 +3,25 @@ use bevy_egui::EguiPlugin;
 
 mod shapes;
 mod voxelization;
+mod voxel_grid;
 mod general_sys;
 mod ui;
 mod schematic;
+mod ron_file;
+mod settings;
 pub mod consts;
 
 // magic angles are 0, 63.5, 17.3
 
 fn main() {
+    let (settings, load_error) = settings::Settings::load();
     App::new()
         .add_plugins(DefaultPlugins.set(WindowPlugin {
-            primary_window: Some(Window {title: "UltVox".into(), focused: true, ..default()}),
+            primary_window: Some(Window {title: "UltVox".into(), focused: true, resolution: settings.window_size.into(), ..default()}),
             ..default()
         }))
         .add_plugin(EguiPlugin)
+        .add_plugin(settings::SettingsPlugin {settings, load_error: load_error.map(|e| e.to_string())})
         .add_plugin(general_sys::GeneralPlugin)
         .add_plugin(ui::UiPlugin)
         .run();
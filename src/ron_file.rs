@@ -0,0 +1,59 @@
+use std::{io::{Read, Write}, path::{Path, PathBuf}};
+
+// Everything that can go wrong reading or writing the files the app keeps, schematics, projects and settings
+#[derive(Debug)]
+pub enum FileError {
+    Io {action: &'static str, path: PathBuf, source: std::io::Error},
+    Ron {path: PathBuf, line: usize, col: usize, msg: String},
+    Serialize(ron::Error)
+}
+
+impl std::fmt::Display for FileError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io {action, path, source} => write!(f, "couldn't {} {}: {}", action, path.display(), source),
+            Self::Ron {path, line, col, msg} => write!(f, "{}:{}:{}: {}", path.display(), line, col, msg),
+            Self::Serialize(e) => write!(f, "couldn't serialize: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for FileError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io {source, ..} => Some(source),
+            Self::Serialize(e) => Some(e),
+            Self::Ron {..} => None,
+        }
+    }
+}
+
+// Creates the parent folder if it's missing
+pub fn write_ron<T: serde::Serialize>(value: &T, path: &Path) -> Result<(), FileError> {
+    let io_err = |action| move |source| FileError::Io {action, path: path.to_path_buf(), source};
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).map_err(io_err("create the folder for"))?;
+    }
+
+    let s = ron::ser::to_string_pretty(value, ron::ser::PrettyConfig::new()).map_err(FileError::Serialize)?;
+    let mut file = std::fs::File::create(path).map_err(io_err("create"))?;
+    file.write_all(s.as_bytes()).map_err(io_err("write"))
+}
+
+pub fn read_ron<T: serde::de::DeserializeOwned>(path: &Path) -> Result<T, FileError> {
+    let s = read_file(path)?;
+    ron::from_str(&s).map_err(|e| FileError::Ron {
+        path: path.to_path_buf(),
+        line: e.position.line,
+        col: e.position.col,
+        msg: e.code.to_string()
+    })
+}
+
+pub fn read_file(path: &Path) -> Result<String, FileError> {
+    let io_err = |action| move |source| FileError::Io {action, path: path.to_path_buf(), source};
+    let mut file = std::fs::File::open(path).map_err(io_err("open"))?;
+    let mut s = String::new();
+    file.read_to_string(&mut s).map_err(io_err("read"))?;
+    Ok(s)
+}
@@ -1,105 +1,190 @@
-use std::{collections::HashMap, io::{Write, Read}};
+use std::{collections::HashMap, path::{Path, PathBuf}};
 
 use bevy::prelude::*;
-use crate::{shapes, voxelization::{self, Voxelizable}};
+use rayon::prelude::*;
+use crate::{ron_file::{self, FileError}, shapes, voxel_grid::{Pos, VoxelGrid}, voxelization::{self, Voxelizable}};
 
-#[derive(Resource, serde::Serialize, serde::Deserialize, Default, Debug)]
+#[derive(Resource, serde::Serialize, serde::Deserialize, Default, Debug, Clone)]
 pub struct Schematic {
     points: HashMap<usize, Vec3>,
-    elements: Vec<Element>
+    elements: Vec<Element>,
+    // group and material names from .obj files, tags has one entry per element or is empty if there are none
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    groups: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    materials: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    tags: Vec<Tag>
 }
 
-impl Schematic {
-    pub fn save_to_file(&self, filename: &str) {
-        let spath = format!("./shapes/{}", filename);
-        let path = std::path::Path::new(&spath);
-        let display = path.display();
-    
-        let mut file = match std::fs::File::create(path) {
-            Err(why) => {
-                println!("couldn't create {}: {}", display, why);
-                return;
-            },
-            Ok(file) => file,
-        };
-        
-        if let Err(why) = file.write(ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::new()).unwrap().as_bytes()) {
-            println!("couldn't write {}: {}", display, why);
+// Group and material of an element, by index into the schematic's names
+#[derive(serde::Serialize, serde::Deserialize, Default, Debug, Clone, Copy, PartialEq, Eq)]
+struct Tag {
+    group: Option<usize>,
+    material: Option<usize>
+}
+
+// Index of the element each voxel came from, the lowest one where elements overlap
+pub type VoxelSources = HashMap<Pos, usize>;
+
+// Everything that can go wrong loading or saving shape files
+#[derive(Debug)]
+pub enum SchematicError {
+    File(FileError),
+    UnknownFormat(PathBuf),
+    Obj {path: PathBuf, line: usize, msg: String}
+}
+
+impl From<FileError> for SchematicError {
+    fn from(e: FileError) -> Self {
+        Self::File(e)
+    }
+}
+
+impl std::fmt::Display for SchematicError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::File(e) => e.fmt(f),
+            Self::UnknownFormat(path) => write!(f, "{} isn't a .ron or .obj file", path.display()),
+            Self::Obj {path, line, msg} => write!(f, "{}:{}: {}", path.display(), line, msg),
         }
     }
+}
 
-    pub fn load_from_file(&mut self, filename: &str) {
-        let spath = format!("./shapes/{}", filename);
-        let path = std::path::Path::new(&spath);
-        let display = path.display();
-    
-        let mut file = match std::fs::File::open(path) {
-            Err(why) => {
-                println!("couldn't open {}: {}", display, why);
-                return;
-            },
-            Ok(file) => file,
+impl std::error::Error for SchematicError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::File(e) => e.source(),
+            _ => None,
+        }
+    }
+}
+
+impl Schematic {
+    pub fn save_to_file(&self, path: &Path) -> Result<(), SchematicError> {
+        Ok(ron_file::write_ron(self, path)?)
+    }
+
+    // Picks the format by extension, on error the loaded schematic is left as it was
+    // Elements with problems are loaded but left out of voxelization, they're returned as diagnostics
+    pub fn load(&mut self, path: &Path) -> Result<Vec<Diagnostic>, SchematicError> {
+        *self = match path.extension().and_then(|e| e.to_str()) {
+            Some("obj") => Self::from_obj_file(path)?,
+            Some("ron") => ron_file::read_ron(path)?,
+            _ => return Err(SchematicError::UnknownFormat(path.to_path_buf())),
         };
+        Ok(self.validate())
+    }
 
-        let mut s = String::new();
+    fn from_obj_file(path: &Path) -> Result<Self, SchematicError> {
+        let s = ron_file::read_file(path)?;
+        Self::parse_obj(&s).map_err(|(line, msg)| SchematicError::Obj {path: path.to_path_buf(), line, msg})
+    }
 
-        match file.read_to_string(&mut s) {
-            Ok(_) => {
-                let s = ron::from_str(&s);
-                match s {
-                    Ok(sch) => *self = sch,
-                    Err(why) => println!("couldn't deserialize {}: {}", display, why),
-                }
+    fn point(&self, id: usize) -> Result<Vec3, Problem> {
+        match self.points.get(&id) {
+            None => Err(Problem::MissingPoint(id)),
+            Some(p) if !p.is_finite() => Err(Problem::NonFinitePoint(id)),
+            Some(p) => Ok(*p),
+        }
+    }
+
+    // None if the element can be voxelized
+    fn check(&self, elem: &Element) -> Option<Problem> {
+        let points = |ids: &[usize]| ids.iter().map(|id| self.point(*id)).collect::<Result<Vec<_>, _>>();
+        match elem {
+            Element::Point(p) => self.point(*p).err(),
+            Element::Tri(p, q, l) => match points(&[*p, *q, *l]) {
+                Ok(v) => degenerate(v[0], v[1], v[2]).then_some(Problem::Degenerate),
+                Err(problem) => Some(problem),
+            },
+            Element::Polygon(v) if v.len() < 3 => Some(Problem::TooFewPoints(v.len())),
+            // single zero area triangles of a fan are skipped while voxelizing, only all of them is a problem
+            Element::Polygon(v) => match points(v) {
+                Ok(v) => (1..v.len()-1).all(|i| degenerate(v[0], v[i], v[i+1])).then_some(Problem::Degenerate),
+                Err(problem) => Some(problem),
             },
-            Err(why) => {println!("couldn't read {}: {}", display, why);},
+            Element::Sphere(_, d) if !(d.is_finite() && *d >= 0.) => Some(Problem::BadSize(*d)),
+            Element::Sphere(p, _) => self.point(*p).err(),
         }
     }
 
-    pub fn load_from_obj_file(&mut self, filename: &str) {
-        let spath = format!("./shapes/{}", filename);
-        let path = std::path::Path::new(&spath);
-        let display = path.display();
-    
-        let mut file = match std::fs::File::open(path) {
-            Err(why) => {
-                println!("couldn't open {}: {}", display, why);
-                return;
+    // Every element that gets skipped by voxelization and why
+    pub fn validate(&self) -> Vec<Diagnostic> {
+        self.elements.iter().enumerate()
+            .filter_map(|(element, elem)| Some(Diagnostic {element, problem: self.check(elem)?}))
+            .collect()
+    }
+
+    fn valid_elements(&self) -> impl Iterator<Item = &Element> {
+        self.elements.iter().filter(|elem| self.check(elem).is_none())
+    }
+
+    pub fn groups(&self) -> &[String] {
+        &self.groups
+    }
+
+    pub fn materials(&self) -> &[String] {
+        &self.materials
+    }
+
+    pub fn group_of(&self, element: usize) -> Option<usize> {
+        self.tags.get(element)?.group
+    }
+
+    pub fn material_of(&self, element: usize) -> Option<usize> {
+        self.tags.get(element)?.material
+    }
+
+    // Vertices, faces, groups ('g' and 'o') and materials ('usemtl'), faces can be 'f 1 2 3' or 'f 1/1/1 2/2/2 3/3/3'
+    // Errors come with the line number
+    fn parse_obj(s: &str) -> Result<Self, (usize, String)> {
+        let mut ret = Self::default();
+        let mut curv = 1;
+        let mut tag = Tag::default();
+        // the same name used again, e.g. switching back to a material, gets the same index
+        let index = |names: &mut Vec<String>, name: &str| match names.iter().position(|n| n == name) {
+            Some(i) => i,
+            None => {
+                names.push(name.to_string());
+                names.len() - 1
             },
-            Ok(file) => file,
         };
-
-        let mut s = String::new();
-
-        match file.read_to_string(&mut s) {
-            Ok(_) => {
-                self.points.clear();
-                self.elements.clear();
-                let mut curv = 1;
-                for line in s.lines() {
-                    let mut parts = line.split_whitespace();
-                    let first = match parts.next() {
-                        Some(s) => s,
-                        None => {continue;},
-                    };
-                    if first == "v" {
-                        let x: f32 = parts.next().unwrap().parse().unwrap();
-                        let y: f32 = parts.next().unwrap().parse().unwrap();
-                        let z: f32 = parts.next().unwrap().parse().unwrap();
-                        self.points.insert(curv, Vec3::new(x, y, z));
-                        curv += 1;
-                    }
-                    else if first == "f" {
-                        let mut pts = Vec::new();
-                        for p in parts {
-                            let point: usize = p.split_once("/").unwrap().0.parse().unwrap();
-                            pts.push(point);
-                        }
-                        self.elements.push(Element::Polygon(pts));
-                    }
+        for (n, line) in s.lines().enumerate() {
+            let mut parts = line.split_whitespace();
+            let first = match parts.next() {
+                Some(s) => s,
+                None => {continue;},
+            };
+            if first == "v" {
+                let mut coord = || -> Result<f32, (usize, String)> {
+                    let c = parts.next().ok_or((n+1, "vertex with less than 3 coordinates".to_string()))?;
+                    c.parse().map_err(|_| (n+1, format!("invalid coordinate '{}'", c)))
+                };
+                let (x, y, z) = (coord()?, coord()?, coord()?);
+                ret.points.insert(curv, Vec3::new(x, y, z));
+                curv += 1;
+            }
+            else if first == "f" {
+                let mut pts = Vec::new();
+                for p in parts {
+                    let index = p.split('/').next().unwrap_or(p);
+                    let point: usize = index.parse().map_err(|_| (n+1, format!("invalid vertex index '{}'", p)))?;
+                    pts.push(point);
                 }
-            },
-            Err(why) => {println!("couldn't read {}: {}", display, why);},
+                ret.elements.push(Element::Polygon(pts));
+                ret.tags.push(tag);
+            }
+            else if first == "g" || first == "o" || first == "usemtl" {
+                let name = parts.collect::<Vec<_>>().join(" ");
+                if first == "usemtl" {tag.material = Some(index(&mut ret.materials, &name));}
+                else {tag.group = Some(index(&mut ret.groups, &name));}
+            }
         }
+        if ret.tags.iter().all(|t| *t == Tag::default()) {
+            ret.tags.clear();
+        }
+        Ok(ret)
     }
 
     pub fn example() -> Self {
@@ -117,9 +202,74 @@ impl Schematic {
 
         s
     }
+
+    // One point per voxel, voxelizing it untransformed gives the voxels back
+    pub fn from_voxels(grid: &VoxelGrid) -> Self {
+        let mut s = Self::default();
+        for (i, p) in grid.iter().enumerate() {
+            s.points.insert(i, Vec3::new(p.0 as f32, p.1 as f32, p.2 as f32));
+            s.elements.push(Element::Point(i));
+        }
+        s
+    }
+}
+
+// Rotation, then per axis scale, then translation, as applied to a schematic before voxelization
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ShapeTransform {
+    pub rot: Quat,
+    pub scale: Vec3,
+    pub offset: Vec3
+}
+
+impl Default for ShapeTransform {
+    fn default() -> Self {
+        Self {rot: Quat::IDENTITY, scale: Vec3::ONE, offset: Vec3::ZERO}
+    }
+}
+
+impl ShapeTransform {
+    pub fn apply(&self, p: Vec3) -> Vec3 {
+        self.rot*p*self.scale + self.offset
+    }
+}
+
+// Zero area, also true for points that are (almost) on one line
+fn degenerate(a: Vec3, b: Vec3, c: Vec3) -> bool {
+    let (ab, ac) = (b - a, c - a);
+    ab.cross(ac).length_squared() <= 1e-12 * ab.length_squared() * ac.length_squared()
+}
+
+// Why an element can't be voxelized
+#[derive(Debug, Clone, PartialEq)]
+pub enum Problem {
+    MissingPoint(usize),
+    NonFinitePoint(usize),
+    Degenerate, // tri or polygon with zero area
+    TooFewPoints(usize), // polygon with less than 3
+    BadSize(f32) // negative or non-finite sphere size
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub element: usize, // index into the schematic's elements
+    pub problem: Problem
+}
+
+impl std::fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "element {}: ", self.element)?;
+        match self.problem {
+            Problem::MissingPoint(id) => write!(f, "uses point {}, which isn't defined", id),
+            Problem::NonFinitePoint(id) => write!(f, "point {} has a NaN or infinite coordinate", id),
+            Problem::Degenerate => write!(f, "has zero area"),
+            Problem::TooFewPoints(n) => write!(f, "polygon with only {} points", n),
+            Problem::BadSize(d) => write!(f, "sphere with size {}", d),
+        }
+    }
 }
 
-#[derive(serde::Serialize, serde::Deserialize, Debug)]
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
 enum Element {
     Point(usize),
     Tri(usize, usize, usize),
@@ -139,79 +289,375 @@ impl Default for Element {
 //     }
 // }
 
+// Untransformed, through the same path as the voxelization the app runs
 impl voxelization::Voxelizable for Schematic {
-    fn voxelize(&self) -> std::collections::HashSet<(i32, i32, i32)> {
-        let mut set = std::collections::HashSet::new();
-        for elem in &self.elements {
-            'macz: {match elem {
-                Element::Point(p) => {
-                    let a = self.points[p];
-                    set.insert((a.x.round() as i32, a.y.round() as i32, a.z.round() as i32));
-                    
-                },
-                Element::Tri(p, q, l) => {
-                    let pts = &self.points;
-                    let (a, b, c) = (pts[p], pts[q], pts[l]);
-                    let tri = shapes::Tri::from_points(a, b, c);
-                    voxelization::merge(&mut set, tri.voxelize());
-                },
-                Element::Polygon(v) => {
-                    if v.len() < 3 {break 'macz;}
-                    let origin = self.points[&v[0]];
-                    for i in 1..(v.len()-1) {
-                        let p1 = self.points[&v[i]];
-                        let p2 = self.points[&v[i+1]];
+    fn voxelize(&self) -> VoxelGrid {
+        // a fresh progress is never cancelled
+        self.voxelize_with_progress(&ShapeTransform::default(), &voxelization::Progress::new(self.work_units())).unwrap_or_default()
+    }
+}
 
-                        let tri = shapes::Tri::from_points(origin, p1, p2);
-                        voxelization::merge(&mut set, tri.voxelize());
-                    }
-                },
-                Element::Sphere(p, d) => {
-                    let p = self.points[p];
-                    let s = shapes::Sphere::new(p, *d);
-                    voxelization::merge(&mut set, s.voxelize());
-                },
-            }}
+impl Schematic {
+    // Centre of the bounding box of all referenced points after rotation and scaling, None if there are no points
+    pub fn transformed_centre(&self, rot: Quat, scale: Vec3) -> Option<Vec3> {
+        let mut min = Vec3::splat(f32::INFINITY);
+        let mut max = Vec3::splat(f32::NEG_INFINITY);
+        let mut extend = |id: &usize, radius: Vec3| {
+            let a = rot*self.points[id]*scale;
+            min = min.min(a - radius);
+            max = max.max(a + radius);
+        };
+        for elem in self.valid_elements() {
+            match elem {
+                Element::Point(p) => extend(p, Vec3::ZERO),
+                Element::Tri(p, q, l) => {for id in [p, q, l] {extend(id, Vec3::ZERO);}},
+                Element::Polygon(v) => {for id in v {extend(id, Vec3::ZERO);}},
+                Element::Sphere(p, d) => extend(p, *d*scale.abs()),
+            }
         }
+        if min.x > max.x {None} else {Some((min + max) / 2.)}
+    }
 
-        set
+    // Units of work reported through voxelization::Progress, one per triangle or other element
+    pub fn work_units(&self) -> usize {
+        self.valid_elements().map(|e| match e {
+            Element::Polygon(v) => v.len().saturating_sub(2),
+            _ => 1
+        }).sum()
     }
-}
 
-impl Schematic {
-    pub fn voxelize_with_transform(&self, rot: Quat, scale: f32) -> std::collections::HashSet<(i32, i32, i32)> {
-        let mut set = std::collections::HashSet::new();
-        for elem in &self.elements {
-            'macz: {match elem {
-                Element::Point(p) => {
-                    let a = rot*self.points[p]*scale;
-                    set.insert((a.x.round() as i32, a.y.round() as i32, a.z.round() as i32));
-                    
-                },
-                Element::Tri(p, q, l) => {
-                    let pts = &self.points;
-                    let (a, b, c) = (rot*pts[p]*scale, rot*pts[q]*scale, rot*pts[l]*scale);
-                    let tri = shapes::Tri::from_points(a, b, c);
-                    voxelization::merge(&mut set, tri.voxelize());
-                },
-                Element::Polygon(v) => {
-                    if v.len() < 3 {break 'macz;}
-                    let origin = rot*self.points[&v[0]]*scale;
-                    for i in 1..(v.len()-1) {
-                        let p1 = rot*self.points[&v[i]]*scale;
-                        let p2 = rot*self.points[&v[i+1]]*scale;
+    // Returns None if cancelled through the progress
+    // Elements are spread over the rayon thread pool, each thread collects into its own set and the sets get merged at the end
+    // Invalid elements are skipped, see validate()
+    pub fn voxelize_with_progress(&self, tf: &ShapeTransform, progress: &voxelization::Progress) -> Option<VoxelGrid> {
+        let set = self.elements.par_iter()
+            .filter(|elem| self.check(elem).is_none())
+            .fold(VoxelGrid::new, |mut set, elem| {
+                if !progress.is_cancelled() {
+                    self.voxelize_element(elem, tf, &mut set, progress);
+                }
+                set
+            })
+            .reduce(VoxelGrid::new, |mut a, b| {
+                voxelization::merge(&mut a, b);
+                a
+            });
+        if progress.is_cancelled() {None} else {Some(set)}
+    }
+
+    // Like voxelize_with_progress, but also keeps which element each voxel came from
+    pub fn voxel_sources(&self, tf: &ShapeTransform, progress: &voxelization::Progress) -> Option<VoxelSources> {
+        let add = |sources: &mut VoxelSources, p: Pos, i: usize| {
+            sources.entry(p).and_modify(|e| *e = (*e).min(i)).or_insert(i);
+        };
+        let sources = self.elements.par_iter().enumerate()
+            .filter(|(_, elem)| self.check(elem).is_none())
+            .fold(VoxelSources::new, |mut sources, (i, elem)| {
+                if !progress.is_cancelled() {
+                    let mut set = VoxelGrid::new();
+                    self.voxelize_element(elem, tf, &mut set, progress);
+                    for p in set.iter() {add(&mut sources, p, i);}
+                }
+                sources
+            })
+            .reduce(VoxelSources::new, |mut a, b| {
+                for (p, i) in b {add(&mut a, p, i);}
+                a
+            });
+        if progress.is_cancelled() {None} else {Some(sources)}
+    }
+
+    fn voxelize_element(&self, elem: &Element, tf: &ShapeTransform, set: &mut VoxelGrid, progress: &voxelization::Progress) {
+        match elem {
+            Element::Point(p) => {
+                let a = tf.apply(self.points[p]);
+                set.insert((a.x.round() as i32, a.y.round() as i32, a.z.round() as i32));
+                progress.advance(1);
+            },
+            Element::Tri(p, q, l) => {
+                let pts = &self.points;
+                let (a, b, c) = (tf.apply(pts[p]), tf.apply(pts[q]), tf.apply(pts[l]));
+                let tri = shapes::Tri::from_points(a, b, c);
+                voxelization::merge(set, tri.voxelize());
+                progress.advance(1);
+            },
+            Element::Polygon(v) => {
+                if v.len() < 3 {return;}
+                let origin = tf.apply(self.points[&v[0]]);
+                for i in 1..(v.len()-1) {
+                    let p1 = tf.apply(self.points[&v[i]]);
+                    let p2 = tf.apply(self.points[&v[i+1]]);
 
+                    if !degenerate(origin, p1, p2) {
                         let tri = shapes::Tri::from_points(origin, p1, p2);
-                        voxelization::merge(&mut set, tri.voxelize());
+                        voxelization::merge(set, tri.voxelize());
+                    }
+                    progress.advance(1);
+                }
+            },
+            Element::Sphere(p, d) => {
+                let p = tf.apply(self.points[p]);
+                let r = *d*tf.scale.abs();
+                if r.x == r.y && r.y == r.z {
+                    let s = shapes::Sphere::new(p, r.x);
+                    voxelization::merge(set, s.voxelize());
+                }
+                else { // non-uniform scale turns the sphere into an axis aligned ellipsoid
+                    let s = shapes::Ellipsoid::new(p, r);
+                    voxelization::merge(set, s.voxelize());
+                }
+                progress.advance(1);
+            },
+        }
+    }
+
+    // Triangles of all tris and polygons after the transform, points and spheres are left out
+    pub fn transformed_triangles(&self, tf: &ShapeTransform) -> Vec<[Vec3; 3]> {
+        let mut tris = Vec::new();
+        for elem in self.valid_elements() {
+            match elem {
+                Element::Tri(p, q, l) => tris.push([p, q, l].map(|id| tf.apply(self.points[id]))),
+                Element::Polygon(v) if v.len() >= 3 => {
+                    let origin = tf.apply(self.points[&v[0]]);
+                    for i in 1..(v.len()-1) {
+                        tris.push([origin, tf.apply(self.points[&v[i]]), tf.apply(self.points[&v[i+1]])]);
                     }
                 },
-                Element::Sphere(p, d) => {
-                    let p = rot*self.points[p]*scale;
-                    let s = shapes::Sphere::new(p, *d*scale);
-                    voxelization::merge(&mut set, s.voxelize());
-                },
-            }}
+                _ => {}
+            }
+        }
+        tris
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::prelude::{Quat, Vec3};
+    use crate::{voxel_grid::VoxelGrid, voxelization::Progress};
+    use crate::ron_file::FileError;
+    use super::{Diagnostic, Element, Problem, Schematic, SchematicError, ShapeTransform};
+
+    fn voxelize(s: &Schematic, scale: Vec3, offset: Vec3) -> VoxelGrid {
+        s.voxelize_with_progress(&ShapeTransform {rot: Quat::IDENTITY, scale, offset}, &Progress::new(s.work_units())).unwrap()
+    }
+
+    // Reference single threaded path for the parallel one
+    fn voxelize_serial(s: &Schematic, rot: Quat, scale: Vec3) -> VoxelGrid {
+        let progress = Progress::new(s.work_units());
+        let mut set = VoxelGrid::new();
+        for elem in &s.elements {
+            s.voxelize_element(elem, &ShapeTransform {rot, scale, offset: Vec3::ZERO}, &mut set, &progress);
         }
         set
     }
+
+    // Latitude-longitude sphere made of n*n quads
+    fn sphere_mesh(n: usize, radius: f32) -> Schematic {
+        let mut s = Schematic::default();
+        for i in 0..=n {
+            let lat = std::f32::consts::PI * i as f32 / n as f32;
+            for j in 0..n {
+                let lon = 2. * std::f32::consts::PI * j as f32 / n as f32;
+                let p = Vec3::new(lat.sin()*lon.cos(), lat.cos(), lat.sin()*lon.sin()) * radius;
+                s.points.insert(i*n + j, p);
+            }
+        }
+        for i in 0..n {
+            for j in 0..n {
+                let j1 = (j + 1) % n;
+                s.elements.push(Element::Polygon(vec![i*n + j, i*n + j1, (i+1)*n + j1, (i+1)*n + j]));
+            }
+        }
+        s
+    }
+
+    #[test]
+    fn centred_square_is_symmetric() {
+        let mut s = Schematic::default();
+        let c = Vec3::new(10.3, 0.2, 7.1);
+        s.points.insert(0, c + Vec3::new(-2.5, 0., -2.5));
+        s.points.insert(1, c + Vec3::new(2.5, 0., -2.5));
+        s.points.insert(2, c + Vec3::new(2.5, 0., 2.5));
+        s.points.insert(3, c + Vec3::new(-2.5, 0., 2.5));
+        s.elements.push(Element::Polygon(vec![0, 1, 2, 3]));
+
+        let centre = s.transformed_centre(Quat::IDENTITY, Vec3::ONE).unwrap();
+        assert!(centre.distance(c) < 0.001);
+
+        let set = voxelize(&s, Vec3::ONE, -centre);
+        assert!(!set.is_empty());
+        for (x, y, z) in set.iter() {
+            assert!(set.contains((-x, y, z)));
+            assert!(set.contains((x, y, -z)));
+        }
+    }
+
+    #[test]
+    fn mirroring_mirrors_voxels() {
+        let mut s = Schematic::default();
+        s.points.insert(0, Vec3::new(0.3, 1., -1.5));
+        s.points.insert(1, Vec3::new(4.1, -1., 0.333));
+        s.points.insert(2, Vec3::new(3., 2.2, 3.666));
+        s.elements.push(Element::Tri(0, 1, 2));
+        s.elements.push(Element::Sphere(2, 2.1));
+
+        let scale = Vec3::new(1.5, 2., 1.);
+        let set = voxelize(&s, scale, Vec3::ZERO);
+        let mirrored = voxelize(&s, scale*Vec3::new(-1., 1., 1.), Vec3::ZERO);
+        assert_eq!(set.len(), mirrored.len());
+        for (x, y, z) in set.iter() {
+            assert!(mirrored.contains((-x, y, z)));
+        }
+    }
+
+    #[test]
+    fn transformed_triangles_fan_polygons() {
+        let s = Schematic::example();
+        let tf = ShapeTransform {offset: Vec3::new(1., 2., 3.), ..Default::default()};
+        let tris = s.transformed_triangles(&tf);
+        assert_eq!(tris.len(), 3); // the tri, then the 4 point polygon as two
+        assert_eq!(tris[1][0], s.points[&0] + tf.offset);
+        assert_eq!(tris[2][2], s.points[&3] + tf.offset);
+    }
+
+    #[test]
+    fn cancelled_voxelization_returns_none() {
+        let s = Schematic::example();
+        let progress = Progress::new(s.work_units());
+        assert_eq!(progress.total(), 5);
+        progress.cancel();
+        assert!(s.voxelize_with_progress(&ShapeTransform::default(), &progress).is_none());
+
+        let progress = Progress::new(s.work_units());
+        assert!(s.voxelize_with_progress(&ShapeTransform::default(), &progress).is_some());
+        assert_eq!(progress.done(), progress.total());
+    }
+
+    #[test]
+    fn parallel_matches_serial() {
+        let mut s = sphere_mesh(24, 15.);
+        let example = Schematic::example();
+        for (k, v) in example.points {
+            s.points.insert(1000 + k, v);
+        }
+        s.elements.push(Element::Point(1000));
+        s.elements.push(Element::Tri(1000, 1001, 1002));
+        s.elements.push(Element::Sphere(1000, 2.1));
+
+        let rot = Quat::from_euler(bevy::prelude::EulerRot::YXZ, 0.3, 1.1, -0.4);
+        let scale = Vec3::new(1.2, 0.8, 1.);
+        let progress = Progress::new(s.work_units());
+        let parallel = s.voxelize_with_progress(&ShapeTransform {rot, scale, offset: Vec3::ZERO}, &progress).unwrap();
+        assert_eq!(parallel, voxelize_serial(&s, rot, scale));
+        assert_eq!(progress.done(), progress.total());
+    }
+
+    #[test]
+    fn parse_obj_faces_and_errors() {
+        let s = Schematic::parse_obj("# comment\nv 0 0 0\nv 1 0 0\nv 0 1 0\nv 1 1 0\n\nf 1 2 3\nf 2/1/1 4/2/2 3/3/3\n").unwrap();
+        assert_eq!(s.points.len(), 4);
+        assert_eq!(s.points[&4], Vec3::new(1., 1., 0.));
+        assert!(matches!(&s.elements[..], [Element::Polygon(a), Element::Polygon(b)] if a == &[1, 2, 3] && b == &[2, 4, 3]));
+
+        assert_eq!(Schematic::parse_obj("v 0 0 0\nv 1 0").unwrap_err(), (2, "vertex with less than 3 coordinates".to_string()));
+        assert_eq!(Schematic::parse_obj("v 0 0 0\n\nf 1 x 3").unwrap_err(), (3, "invalid vertex index 'x'".to_string()));
+        assert!(s.tags.is_empty());
+
+        let s = Schematic::parse_obj("v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 3\ng left wing\nusemtl red\nf 1 2 3\nusemtl blue\nf 1 2 3\ng tail\nusemtl red\nf 1 2 3\n").unwrap();
+        assert_eq!(s.groups, vec!["left wing", "tail"]);
+        assert_eq!(s.materials, vec!["red", "blue"]);
+        let tags: Vec<_> = (0..4).map(|i| (s.group_of(i), s.material_of(i))).collect();
+        assert_eq!(tags, vec![(None, None), (Some(0), Some(0)), (Some(0), Some(1)), (Some(1), Some(0))]);
+    }
+
+    #[test]
+    fn sources_cover_the_voxels() {
+        let s = sphere_mesh(12, 6.);
+        let tf = ShapeTransform {rot: Quat::IDENTITY, scale: Vec3::ONE, offset: Vec3::splat(0.3)};
+        let sources = s.voxel_sources(&tf, &Progress::new(s.work_units())).unwrap();
+        let grid = s.voxelize_with_progress(&tf, &Progress::new(s.work_units())).unwrap();
+        assert_eq!(sources.keys().copied().collect::<VoxelGrid>(), grid);
+        // the lowest element wins, each element's voxels that aren't claimed by an earlier one are its own
+        for (p, i) in sources.iter() {
+            let mut set = VoxelGrid::new();
+            s.voxelize_element(&s.elements[*i], &tf, &mut set, &Progress::new(s.work_units()));
+            assert!(set.contains(*p));
+        }
+    }
+
+    #[test]
+    fn voxels_as_points() {
+        let grid: VoxelGrid = [(0, 0, 0), (-3, 2, 7), (1, -5, 1)].into_iter().collect();
+        let s = Schematic::from_voxels(&grid);
+        assert!(s.validate().is_empty());
+        assert_eq!(voxelize(&s, Vec3::ONE, Vec3::ZERO), grid);
+    }
+
+    #[test]
+    fn save_and_load_paths() {
+        let dir = std::env::temp_dir().join(format!("ultvox_test_{}", std::process::id())).join("sub");
+        let path = dir.join("example.ron");
+        Schematic::example().save_to_file(&path).unwrap();
+
+        let mut s = Schematic::default();
+        s.load(&path).unwrap();
+        assert_eq!(s.points.len(), 4);
+        assert_eq!(s.elements.len(), 4);
+
+        // failed loads leave what was loaded alone
+        assert!(matches!(s.load(&dir.join("missing.obj")), Err(SchematicError::File(FileError::Io {action: "open", ..}))));
+        assert!(matches!(s.load(&dir.join("notes.txt")), Err(SchematicError::UnknownFormat(_))));
+        std::fs::write(dir.join("broken.ron"), "(points: {},\n elements: [Bad])").unwrap();
+        assert!(matches!(s.load(&dir.join("broken.ron")), Err(SchematicError::File(FileError::Ron {line: 2, ..}))));
+        assert_eq!(s.elements.len(), 4);
+        std::fs::remove_dir_all(dir.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn invalid_elements_are_reported_and_skipped() {
+        let mut s = Schematic::example();
+        s.points.insert(10, Vec3::new(f32::NAN, 0., 0.));
+        s.points.insert(11, Vec3::new(2., 2., 2.));
+        s.elements.push(Element::Tri(0, 1, 7));
+        s.elements.push(Element::Point(10));
+        s.elements.push(Element::Tri(0, 0, 1));
+        s.elements.push(Element::Polygon(vec![0, 1]));
+        s.elements.push(Element::Sphere(0, -1.));
+        s.elements.push(Element::Polygon(vec![1, 11, 1, 11]));
+
+        let problems: Vec<_> = s.validate().into_iter().map(|Diagnostic {element, problem}| (element, problem)).collect();
+        assert_eq!(problems, vec![
+            (4, Problem::MissingPoint(7)),
+            (5, Problem::NonFinitePoint(10)),
+            (6, Problem::Degenerate),
+            (7, Problem::TooFewPoints(2)),
+            (8, Problem::BadSize(-1.)),
+            (9, Problem::Degenerate),
+        ]);
+
+        // the valid elements voxelize the same as without the invalid ones
+        assert_eq!(s.work_units(), Schematic::example().work_units());
+        assert_eq!(voxelize(&s, Vec3::ONE, Vec3::ZERO), voxelize(&Schematic::example(), Vec3::ONE, Vec3::ZERO));
+        assert_eq!(s.transformed_centre(Quat::IDENTITY, Vec3::ONE), Schematic::example().transformed_centre(Quat::IDENTITY, Vec3::ONE));
+    }
+
+    // Run with: cargo test --release -- --ignored --nocapture bench_
+    #[test]
+    #[ignore]
+    fn bench_parallel_voxelization() {
+        let s = sphere_mesh(400, 150.);
+        let rot = Quat::from_euler(bevy::prelude::EulerRot::YXZ, 0.3, 1.1, -0.4);
+
+        let start = std::time::Instant::now();
+        let serial = voxelize_serial(&s, rot, Vec3::ONE);
+        let serial_time = start.elapsed();
+
+        let start = std::time::Instant::now();
+        let parallel = s.voxelize_with_progress(&ShapeTransform {rot, ..Default::default()}, &Progress::new(s.work_units())).unwrap();
+        let parallel_time = start.elapsed();
+
+        println!("{} triangles, {} voxels", s.work_units(), serial.len());
+        println!("serial: {:?}, parallel: {:?}, speed-up: {:.2}x", serial_time, parallel_time, serial_time.as_secs_f64() / parallel_time.as_secs_f64());
+        assert_eq!(serial, parallel);
+    }
 }
@@ -0,0 +1,213 @@
+use std::path::Path;
+
+use bevy::{prelude::*, input::InputSystem, window::PrimaryWindow};
+
+use crate::{consts, ron_file::{self, FileError}, ui::UiState};
+
+// Loaded in main, the window size is needed before the window is created
+pub struct SettingsPlugin {
+    pub settings: Settings,
+    pub load_error: Option<String>
+}
+
+impl Plugin for SettingsPlugin {
+    fn build(&self, app: &mut App) {
+        let load_error = self.load_error.clone();
+        app
+            .insert_resource(self.settings.clone())
+            .insert_resource(KeyCapture::default())
+            .add_startup_system(move |mut ui_state: ResMut<UiState>| {
+                if let Some(why) = &load_error {ui_state.notify_error(why, None);}
+            })
+            .add_system(capture_key_system.in_base_set(CoreSet::PreUpdate).after(InputSystem))
+            .add_system(window_size_system.run_if(resource_changed::<Settings>()));
+    }
+}
+
+// User settings kept in consts::SETTINGS_PATH, the consts are the defaults
+#[derive(Resource, serde::Serialize, serde::Deserialize, Clone, PartialEq, Debug)]
+#[serde(default)] // settings missing from the file keep their default, so files from older versions still load
+pub struct Settings {
+    pub cam_speed: f32, // speed of wasd movement
+    pub shift_boost: f32, // how much faster holding boost is
+    pub mouse_sens: f32, // radians per pixel
+    pub window_size: (f32, f32),
+    pub keys: KeyBindings
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            cam_speed: consts::CAM_SPEED,
+            shift_boost: consts::SHIFT_BOOST,
+            mouse_sens: consts::MOUSE_SENS,
+            window_size: (consts::BASE_WINDOW_X, consts::BASE_WINDOW_Y),
+            keys: KeyBindings::default()
+        }
+    }
+}
+
+impl Settings {
+    // A missing file gives the defaults, a broken one the defaults and the error
+    pub fn load() -> (Self, Option<FileError>) {
+        let path = Path::new(consts::SETTINGS_PATH);
+        if !path.exists() {
+            return (Self::default(), None);
+        }
+        match ron_file::read_ron(path) {
+            Ok(settings) => (settings, None),
+            Err(why) => (Self::default(), Some(why)),
+        }
+    }
+
+    pub fn save(&self) -> Result<(), FileError> {
+        ron_file::write_ron(self, Path::new(consts::SETTINGS_PATH))
+    }
+}
+
+// What the remappable keys do, any of an action's keys triggers it
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Action {
+    Forward,
+    Back,
+    Left,
+    Right,
+    Up,
+    Down,
+    Boost,
+    ModeSwitch
+}
+
+impl Action {
+    pub const ALL: [Action; 8] = [Self::Forward, Self::Back, Self::Left, Self::Right, Self::Up, Self::Down, Self::Boost, Self::ModeSwitch];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Forward => "Forward",
+            Self::Back => "Back",
+            Self::Left => "Left",
+            Self::Right => "Right",
+            Self::Up => "Up",
+            Self::Down => "Down",
+            Self::Boost => "Speed boost",
+            Self::ModeSwitch => "Menu/camera mode",
+        }
+    }
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, PartialEq, Debug)]
+#[serde(default)]
+pub struct KeyBindings {
+    forward: Vec<KeyCode>,
+    back: Vec<KeyCode>,
+    left: Vec<KeyCode>,
+    right: Vec<KeyCode>,
+    up: Vec<KeyCode>,
+    down: Vec<KeyCode>,
+    boost: Vec<KeyCode>,
+    mode_switch: Vec<KeyCode>
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        Self {
+            forward: vec![KeyCode::W, KeyCode::Up],
+            back: vec![KeyCode::S, KeyCode::Down],
+            left: vec![KeyCode::A, KeyCode::Left],
+            right: vec![KeyCode::D, KeyCode::Right],
+            up: vec![KeyCode::Space],
+            down: vec![KeyCode::LControl],
+            boost: vec![KeyCode::LShift],
+            mode_switch: vec![consts::MODE_SWITCH]
+        }
+    }
+}
+
+impl KeyBindings {
+    pub fn keys(&self, action: Action) -> &[KeyCode] {
+        match action {
+            Action::Forward => &self.forward,
+            Action::Back => &self.back,
+            Action::Left => &self.left,
+            Action::Right => &self.right,
+            Action::Up => &self.up,
+            Action::Down => &self.down,
+            Action::Boost => &self.boost,
+            Action::ModeSwitch => &self.mode_switch,
+        }
+    }
+
+    pub fn keys_mut(&mut self, action: Action) -> &mut Vec<KeyCode> {
+        match action {
+            Action::Forward => &mut self.forward,
+            Action::Back => &mut self.back,
+            Action::Left => &mut self.left,
+            Action::Right => &mut self.right,
+            Action::Up => &mut self.up,
+            Action::Down => &mut self.down,
+            Action::Boost => &mut self.boost,
+            Action::ModeSwitch => &mut self.mode_switch,
+        }
+    }
+
+    pub fn pressed(&self, input: &Input<KeyCode>, action: Action) -> bool {
+        input.any_pressed(self.keys(action).iter().copied())
+    }
+
+    pub fn just_pressed(&self, input: &Input<KeyCode>, action: Action) -> bool {
+        input.any_just_pressed(self.keys(action).iter().copied())
+    }
+}
+
+// The next key pressed gets bound to the action, replacing the key at the index or added if None
+#[derive(Resource, Default)]
+pub struct KeyCapture(pub Option<(Action, Option<usize>)>);
+
+// Runs before everything else reads the keyboard, so the captured key doesn't also do what it's bound to
+fn capture_key_system(mut capture: ResMut<KeyCapture>, mut settings: ResMut<Settings>, mut keyboard: ResMut<Input<KeyCode>>) {
+    let Some((action, slot)) = capture.0 else {return;};
+    let Some(key) = keyboard.get_just_pressed().next().copied() else {return;};
+    keyboard.reset(key);
+    capture.0 = None;
+    if key == KeyCode::Escape {
+        return; // cancels
+    }
+    let keys = settings.keys.keys_mut(action);
+    match slot {
+        Some(i) if i < keys.len() => keys[i] = key,
+        _ => if !keys.contains(&key) {keys.push(key);},
+    }
+}
+
+// Only when the setting changes, resizing the window by hand is left alone
+fn window_size_system(settings: Res<Settings>, mut applied: Local<Option<(f32, f32)>>, mut windows: Query<&mut Window, With<PrimaryWindow>>) {
+    if *applied == Some(settings.window_size) {
+        return;
+    }
+    *applied = Some(settings.window_size);
+    let Ok(mut window) = windows.get_single_mut() else {return;};
+    let (w, h) = settings.window_size;
+    if (window.width(), window.height()) != (w, h) {
+        window.resolution.set(w, h);
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use bevy::prelude::KeyCode;
+    use super::{Action, Settings};
+
+    #[test]
+    fn missing_settings_keep_defaults() {
+        let settings: Settings = ron::from_str("(cam_speed: 5.0, keys: (mode_switch: [Tab, F1]))").unwrap();
+        let default = Settings::default();
+        assert_eq!(settings.cam_speed, 5.);
+        assert_eq!(settings.mouse_sens, default.mouse_sens);
+        assert_eq!(settings.keys.keys(Action::ModeSwitch), &[KeyCode::Tab, KeyCode::F1]);
+        assert_eq!(settings.keys.keys(Action::Forward), default.keys.keys(Action::Forward));
+
+        let again: Settings = ron::from_str(&ron::to_string(&settings).unwrap()).unwrap();
+        assert_eq!(settings, again);
+    }
+}
@@ -167,6 +167,48 @@ impl Sphere {
     }
 }
 
+// Axis aligned ellipsoid shell, what a sphere becomes under non-uniform scaling
+pub struct Ellipsoid {
+    p: Vec3,
+    r: Vec3 // radii along x, y, z
+}
+
+impl Ellipsoid {
+    pub fn new(p: Vec3, r: Vec3) -> Self {
+        Self {p, r}
+    }
+
+    pub fn points_inside(&self) -> Vec<Vec3> {
+        let mut set = Vec::new();
+        let min = (self.p - self.r).ceil();
+        let max = (self.p + self.r).floor();
+
+        for x in min.x as i32-1..=max.x as i32+1 {
+            for y in min.y as i32-1..=max.y as i32+1 {
+                for z in min.z as i32-1..=max.z as i32+1 {
+                    let v = Vec3::new(x as f32, y as f32, z as f32);
+                    if self.contains(v) {
+                        set.push(v);
+                    }
+                }
+            }
+        }
+
+        set
+    }
+
+    // Uses the distance to the surface along the ray from the centre, which is exact for spheres
+    pub fn contains(&self, p: Vec3) -> bool {
+        let diff = p - self.p;
+        let k = (diff / self.r).length(); // 1 on the surface
+        let dist = diff.length();
+        if k == 0. {
+            return self.r.min_element() <= (3_f32).sqrt()/2.;
+        }
+        (dist - dist/k).abs() <= (3_f32).sqrt()/2.
+    }
+}
+
 
 #[cfg(test)]
 mod tests {
@@ -1,8 +1,11 @@
-use std::{fs::read_dir, path::Path};
+use std::{fs::read_dir, path::{Path, PathBuf}};
 
-use bevy::prelude::*;
+use bevy::{prelude::*, ecs::system::SystemParam, window::PrimaryWindow};
 use bevy_egui::{egui::{self, Context}, EguiContexts};
-use crate::{general_sys::{AppState, ReloadVoxelsEvent, RotationConfig}, schematic::Schematic};
+use save_dialog::{SaveDialog, SaveKind};
+use crate::{consts, voxel_grid::Pos, general_sys::{AppState, CentreMode, ReloadVoxelsEvent, RotationConfig, VoxelizeJob, Voxels, colouring::{Colouring, Scheme}, components::{Components, Connectivity}, camera::{CameraMode, ViewEvent, ViewPreset}, edit::EditEvent, files::OpenFileEvent, history::{History, HistoryEvent}, measure::Measure, overhang::{ExportScaffoldingEvent, Support}, overlay::OverlayMode, picking::{Picked, Tool}, project::{self, ProjectEvent}}, schematic::Schematic, settings::{Action, KeyCapture, Settings}};
+
+mod save_dialog;
 
 pub struct UiPlugin;
 
@@ -10,6 +13,12 @@ impl Plugin for UiPlugin {
     fn build(&self, app: &mut App) {
         app.insert_resource(UiState::default());
         app.add_system(draw_ui_system.in_set(OnUpdate(AppState::Ui)));
+        app.add_system(status_bar_system.in_set(OnUpdate(AppState::Ui)).before(draw_ui_system));
+        app.add_system(history_window_system.in_set(OnUpdate(AppState::Ui)).after(draw_ui_system));
+        app.add_system(settings_window_system.in_set(OnUpdate(AppState::Ui)).after(draw_ui_system));
+        app.add_system(colours_window_system.in_set(OnUpdate(AppState::Ui)).after(draw_ui_system));
+        app.add_system(components_window_system.in_set(OnUpdate(AppState::Ui)).after(draw_ui_system));
+        app.add_system(notifications_system.after(draw_ui_system));
     }
 }
 
@@ -17,79 +26,261 @@ impl Plugin for UiPlugin {
 pub struct UiState {
     ewindow_open: bool,
     helpwindow_open: bool,
-    ron_files: Vec<String>,
-    obj_files: Vec<String>,
-    uptoy_slider: i32,
+    pub shapes_dir: PathBuf,
+    shapes_dir_edit: String, // text in the folder field of the load menu
+    shapes: ShapeFolder,
+    opened: Vec<PathBuf>, // files loaded from outside the shapes folder
+    clipwindow_open: bool,
+    historywindow_open: bool,
+    settingswindow_open: bool,
+    colourwindow_open: bool,
+    componentwindow_open: bool,
+    clip: [AxisClip; 3], // x, y, z
+    pub overlay: OverlayMode,
     pub voxel_count: usize,
+    pub support: Support, // what counts as holding up a voxel in the overhang check
+    pub show_overhangs: bool,
+    pub overhang_count: usize,
+    pub show_scaffolding: bool,
+    pub scaffold_count: usize,
+    pub connectivity: Connectivity,
+    min_component: usize, // components smaller than this get deleted
+    pub auto_reload: bool, // voxelize again by itself when the transform settings change
+    pub current_file: Option<PathBuf>, // file the schematic was loaded from
+    pub stale: bool, // the loaded file failed to load again, what's shown is from the last version that loaded
+    notifications: Vec<Notification>,
+    save_dialog: SaveDialog,
+}
+
+// Shown in the bottom right corner until dismissed
+struct Notification {
+    text: String,
+    error: bool,
+    file: Option<PathBuf> // file the message is about, dropped once that file loads fine
+}
+
+// Voxels are shown between min and max, the first `preview` layers past max are transparent
+#[derive(Clone, Copy, PartialEq, Eq, Debug, serde::Serialize, serde::Deserialize)]
+pub struct AxisClip {
+    min: i32,
+    max: i32,
+    preview: i32
+}
+
+impl AxisClip {
+    fn new(preview: i32) -> Self {
+        Self {min: i32::MIN, max: i32::MAX, preview}
+    }
+
+    fn state(&self, v: i32) -> ClipState {
+        if v < self.min || v > self.max.saturating_add(self.preview) {ClipState::Hidden}
+        else if v > self.max {ClipState::Preview}
+        else {ClipState::Visible}
+    }
+}
+
+pub enum ClipState {
+    Visible,
+    Preview,
+    Hidden
+}
+
+// Shapes and projects, the files listed in the load menu
+pub fn is_loadable_file(path: &Path) -> bool {
+    matches!(path.extension().and_then(|e| e.to_str()), Some("ron" | "obj" | project::EXTENSION))
+}
+
+// Shape files in a folder, subfolders without any are left out
+#[derive(Default)]
+struct ShapeFolder {
+    name: String,
+    folders: Vec<ShapeFolder>,
+    files: Vec<PathBuf>
+}
+
+impl ShapeFolder {
+    fn scan(path: &Path) -> std::io::Result<Self> {
+        let mut ret = Self {name: path.file_name().map_or(path.display().to_string(), |n| n.to_string_lossy().into_owned()), ..default()};
+        for entry in read_dir(path)? {
+            let entry = entry?;
+            let path = entry.path();
+            // file_type doesn't follow symlinks, so a link back up the tree can't recurse forever
+            if entry.file_type()?.is_dir() {
+                if let Ok(folder) = Self::scan(&path) {
+                    if !folder.is_empty() {ret.folders.push(folder);}
+                }
+            }
+            else if is_loadable_file(&path) {
+                ret.files.push(path);
+            }
+        }
+        ret.folders.sort_by(|a, b| a.name.cmp(&b.name));
+        ret.files.sort();
+        Ok(ret)
+    }
+
+    fn is_empty(&self) -> bool {
+        self.folders.is_empty() && self.files.is_empty()
+    }
+
+    // Subfolders as submenus, then the files
+    fn menu(&self, ui: &mut egui::Ui, clicked: &mut Option<PathBuf>) {
+        for folder in self.folders.iter() {
+            ui.menu_button(&folder.name, |ui| folder.menu(ui, clicked));
+        }
+        for file in self.files.iter() {
+            let name = file.file_name().unwrap_or_default().to_string_lossy();
+            if ui.button(name).clicked() {
+                *clicked = Some(file.clone());
+            }
+        }
+    }
 }
 
 impl Default for UiState {
     fn default() -> Self {
-        let mut ret = Self {ewindow_open: false, helpwindow_open: false, ron_files: Vec::new(), obj_files: Vec::new(), uptoy_slider: 0, voxel_count: 0};
+        let shapes_dir = PathBuf::from("./shapes");
+        let mut ret = Self {
+            ewindow_open: false, helpwindow_open: false, clipwindow_open: false, historywindow_open: false, settingswindow_open: false, colourwindow_open: false, componentwindow_open: false,
+            shapes_dir_edit: shapes_dir.display().to_string(), shapes_dir,
+            shapes: ShapeFolder::default(), opened: Vec::new(),
+            clip: [AxisClip::new(0), AxisClip::new(2), AxisClip::new(0)],
+            overlay: OverlayMode::Off,
+            voxel_count: 0,
+            support: Support::default(),
+            show_overhangs: false,
+            overhang_count: 0,
+            show_scaffolding: false,
+            scaffold_count: 0,
+            connectivity: Connectivity::default(),
+            min_component: 2,
+            auto_reload: true,
+            current_file: None,
+            stale: false,
+            notifications: Vec::new(),
+            save_dialog: SaveDialog::load(Path::new(consts::SAVE_DIALOG_PATH))
+        };
         ret.reload_files();
         ret
     }
 }
 
 impl UiState {
-    fn reload_files(&mut self) {
-        let spath = "./shapes/";
-        let path = Path::new(&spath);
-        let display = path.display();
-    
-        if !path.is_dir() {
-            println!("Path {} isn't a directory somehow", display);
-            return;
-        }
-        let dirs = match read_dir(path) {
-            Ok(dir) => dir,
+    pub fn reload_files(&mut self) {
+        match ShapeFolder::scan(&self.shapes_dir) {
+            Ok(folder) => self.shapes = folder,
             Err(why) => {
-                println!("couldn't read directory {}: {}", display, why);
-                return;
+                self.shapes = ShapeFolder::default();
+                self.notify_error(format!("couldn't read the shapes folder {}: {}", self.shapes_dir.display(), why), None);
             },
-        };
+        }
+    }
 
-        self.obj_files.clear();
-        self.ron_files.clear();
+    // The same message isn't shown twice
+    fn notify(&mut self, text: String, error: bool, file: Option<&Path>) {
+        if !self.notifications.iter().any(|n| n.text == text) {
+            self.notifications.push(Notification {text, error, file: file.map(Path::to_path_buf)});
+        }
+    }
 
-        for entry in dirs {
-            match entry {
-                Ok(e) => {
-                    let fname = e.file_name().into_string().expect("Somehow invalid file name");
-                    if fname.ends_with(".ron") {
-                        self.ron_files.push(fname);
-                    }
-                    else if fname.ends_with(".obj") {
-                        self.obj_files.push(fname);
-                    }
-                },
-                Err(why) => {
-                    println!("Error on dir entry: {}", why);
-                },
+    pub fn notify_error(&mut self, text: impl std::fmt::Display, file: Option<&Path>) {
+        self.notify(text.to_string(), true, file);
+    }
+
+    pub fn notify_info(&mut self, text: impl std::fmt::Display) {
+        self.notify(text.to_string(), false, None);
+    }
+
+    // After writing to a path picked in the save dialog
+    pub fn saved(&mut self, path: PathBuf) {
+        self.notify_info(format!("Saved to {}", path.display()));
+        if let Err(why) = self.save_dialog.saved(path) {self.notify_error(why, None);}
+    }
+
+    // Errors about a file that since loaded fine are out of date
+    pub fn file_loaded(&mut self, path: &Path) {
+        self.notifications.retain(|n| n.file.as_deref() != Some(path));
+    }
+
+    pub fn set_shapes_dir(&mut self, path: PathBuf) {
+        self.shapes_dir_edit = path.display().to_string();
+        self.shapes_dir = path;
+        self.reload_files();
+    }
+
+    // Remembers files that wouldn't show up in the load menu otherwise
+    pub fn add_opened(&mut self, path: &Path) {
+        if !path.starts_with(&self.shapes_dir) && !self.opened.iter().any(|p| p == path) {
+            self.opened.push(path.to_path_buf());
+        }
+    }
+
+    pub fn componentwindow_open(&self) -> bool {
+        self.componentwindow_open
+    }
+
+    pub fn clip_settings(&self) -> [AxisClip; 3] {
+        self.clip
+    }
+
+    pub fn set_clip(&mut self, clip: [AxisClip; 3]) {
+        self.clip = clip;
+    }
+
+    // Hidden on any axis hides the voxel, otherwise preview on any axis makes it transparent
+    pub fn clip_state(&self, p: Pos) -> ClipState {
+        let mut ret = ClipState::Visible;
+        for (clip, v) in self.clip.iter().zip([p.0, p.1, p.2]) {
+            match clip.state(v) {
+                ClipState::Hidden => return ClipState::Hidden,
+                ClipState::Preview => ret = ClipState::Preview,
+                ClipState::Visible => {},
             }
         }
+        ret
     }
 
-    pub fn get_uptoy(&self) -> i32 {
-        self.uptoy_slider
+    // Clip values sitting at the edge of the old bounds (so not cutting anything) follow the new bounds
+    pub fn fit_clip(&mut self, old: (Pos, Pos), new: (Pos, Pos)) {
+        let olds = [(old.0.0, old.1.0), (old.0.1, old.1.1), (old.0.2, old.1.2)];
+        let news = [(new.0.0, new.1.0), (new.0.1, new.1.1), (new.0.2, new.1.2)];
+        for ((clip, old), new) in self.clip.iter_mut().zip(olds).zip(news) {
+            if clip.min <= old.0 {clip.min = new.0;}
+            if clip.max >= old.1 {clip.max = new.1;}
+        }
     }
 }
 
+// Events the ui sends, bundled to keep the system signatures short
+#[derive(SystemParam)]
+struct UiEvents<'w> {
+    reloader: EventWriter<'w, ReloadVoxelsEvent>,
+    opener: EventWriter<'w, OpenFileEvent>,
+    views: EventWriter<'w, ViewEvent>,
+    projects: EventWriter<'w, ProjectEvent>,
+    scaffolding: EventWriter<'w, ExportScaffoldingEvent>
+}
+
 fn draw_ui_system(
     mut ctx: EguiContexts,
     mut ui_state: ResMut<UiState>,
-    mut schematic: ResMut<Schematic>,
+    schematic: Res<Schematic>,
     mut rot_con: ResMut<RotationConfig>,
-    reloader: EventWriter<ReloadVoxelsEvent>
+    job: Res<VoxelizeJob>,
+    mut cam_mode: ResMut<CameraMode>,
+    mut events: UiEvents
 ) {
     let c = ctx.ctx_mut();
     let u = ui_state.as_mut();
-    let s = schematic.as_mut();
     let rc = rot_con.as_mut();
 
-    top_panel(c, u, s, rc, reloader);
+    let mut mode = *cam_mode;
+    top_panel(c, u, rc, &job, &mut mode, &mut events);
+    if mode != *cam_mode {*cam_mode = mode;} // avoid triggering change detection every frame
+
+    edit_window(c, u, &schematic, &mut events);
 
-    edit_window(c, u, s);
+    clip_window(c, u, rc);
 
     help_window(c, u);
 }
@@ -97,76 +288,584 @@ fn draw_ui_system(
 fn top_panel(
     ctx: &mut Context,
     ui_state: &mut UiState,
-    schematic: &mut Schematic,
     rot_con: &mut RotationConfig,
-    mut reloader: EventWriter<ReloadVoxelsEvent>
+    job: &VoxelizeJob,
+    cam_mode: &mut CameraMode,
+    events: &mut UiEvents
 ) {
-    let UiState {ewindow_open, helpwindow_open, ron_files, obj_files, uptoy_slider, voxel_count} = ui_state;
-    let RotationConfig {scale, rotx, roty, rotz, yrange, .. } = rot_con;
+    let UiEvents {reloader, opener, views, ..} = events;
+    let UiState {ewindow_open, helpwindow_open, clipwindow_open, historywindow_open, settingswindow_open, colourwindow_open, componentwindow_open, shapes_dir, shapes_dir_edit, shapes, opened, clip, overlay, voxel_count, support, show_overhangs, overhang_count, show_scaffolding, scaffold_count, auto_reload, stale, ..} = ui_state;
+    let RotationConfig {scale, mirror, rotx, roty, rotz, offset, centre, bounds, .. } = rot_con;
     let mut refresh_state = false;
+    let mut new_dir = None;
     egui::TopBottomPanel::top("top_panel").show(ctx, |ui| {
         ui.horizontal(|ui| {
             ui.menu_button("Load schematic", |ui| {
                 if ui.button("Refresh").clicked() {
                     refresh_state = true;
                 }
-                ui.separator();
-                for ron_file in ron_files.iter() {
-                    if ui.button(ron_file).clicked() {
-                        schematic.load_from_file(ron_file);
-                        reloader.send(ReloadVoxelsEvent);
-                        ui.close_menu();
+                ui.horizontal(|ui| {
+                    ui.label("Folder:");
+                    let edit = ui.text_edit_singleline(shapes_dir_edit);
+                    if edit.lost_focus() && Path::new(shapes_dir_edit.as_str()) != shapes_dir.as_path() {
+                        new_dir = Some(PathBuf::from(shapes_dir_edit.as_str()));
+                    }
+                });
+                let mut clicked = None;
+                if !opened.is_empty() {
+                    ui.separator();
+                    for path in opened.iter() {
+                        let name = path.file_name().unwrap_or_default().to_string_lossy();
+                        if ui.button(name).on_hover_text(path.display().to_string()).clicked() {clicked = Some(path.clone());}
                     }
                 }
                 ui.separator();
-                for obj_file in obj_files.iter() {
-                    if ui.button(obj_file).clicked() {
-                        schematic.load_from_obj_file(obj_file);
-                        reloader.send(ReloadVoxelsEvent);
-                        ui.close_menu();
-                    }
+                if shapes.is_empty() {
+                    ui.label("No .ron, .obj or .ultvox files in the folder");
+                }
+                shapes.menu(ui, &mut clicked);
+                if let Some(path) = clicked {
+                    opener.send(OpenFileEvent(path));
+                    ui.close_menu();
                 }
             });
             ui.separator();
-            if ui.button("Reset rotations").clicked() {*scale = 1.; *rotx = 0.; *roty = 0.; *rotz = 0.;}
+            if ui.button("Reset rotations").clicked() {*scale = Vec3::ONE; *mirror = (false, false, false); *rotx = 0.; *roty = 0.; *rotz = 0.; *offset = Vec3::ZERO;}
             ui.separator();
             ui.label("Rotations (ypr):");
             ui.drag_angle(rotx);
             ui.drag_angle(roty);
             ui.drag_angle(rotz);
-            ui.label("Scale:"); ui.add(egui::DragValue::new(scale).speed(0.05));
+            ui.label("Scale:");
+            ui.add(egui::DragValue::new(&mut scale.x).speed(0.05).clamp_range(0.01..=f32::MAX));
+            ui.add(egui::DragValue::new(&mut scale.y).speed(0.05).clamp_range(0.01..=f32::MAX));
+            ui.add(egui::DragValue::new(&mut scale.z).speed(0.05).clamp_range(0.01..=f32::MAX));
+            ui.label("Mirror:");
+            ui.toggle_value(&mut mirror.0, "X");
+            ui.toggle_value(&mut mirror.1, "Y");
+            ui.toggle_value(&mut mirror.2, "Z");
+            ui.label("Offset:");
+            ui.add(egui::DragValue::new(&mut offset.x).speed(0.05));
+            ui.add(egui::DragValue::new(&mut offset.y).speed(0.05));
+            ui.add(egui::DragValue::new(&mut offset.z).speed(0.05));
+            egui::ComboBox::from_id_source("centre_mode")
+                .selected_text(match centre {
+                    CentreMode::None => "No centring",
+                    CentreMode::VoxelCentre => "Centre on voxel",
+                    CentreMode::VoxelCorner => "Centre on corner",
+                })
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(centre, CentreMode::None, "No centring");
+                    ui.selectable_value(centre, CentreMode::VoxelCentre, "Centre on voxel");
+                    ui.selectable_value(centre, CentreMode::VoxelCorner, "Centre on corner");
+                });
             ui.label("Up to Y: "); 
             ui.add(
-                egui::Slider::new(uptoy_slider, (yrange.0-1)..=yrange.1)
+                egui::Slider::new(&mut clip[1].max, (bounds.0.1-1)..=bounds.1.1)
                     .integer()
             );
             if ui.button("Reload voxels").clicked() {reloader.send(ReloadVoxelsEvent);}
+            ui.checkbox(auto_reload, "Auto");
+            if job.is_running() {
+                let progress = job.progress();
+                ui.add(
+                    egui::ProgressBar::new(progress.fraction())
+                        .desired_width(120.)
+                        .text(format!("{}/{}", progress.done(), progress.total()))
+                );
+                if ui.button("Cancel").clicked() {job.cancel();}
+            }
+            ui.separator();
+            ui.label("Overlay:");
+            egui::ComboBox::from_id_source("overlay_mode")
+                .selected_text(match overlay {
+                    OverlayMode::Off => "Off",
+                    OverlayMode::Wireframe => "Wireframe",
+                    OverlayMode::Translucent => "Translucent",
+                })
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(overlay, OverlayMode::Off, "Off");
+                    ui.selectable_value(overlay, OverlayMode::Wireframe, "Wireframe");
+                    ui.selectable_value(overlay, OverlayMode::Translucent, "Translucent");
+                });
             ui.separator();
             ui.label(format!("Voxel count: {}", voxel_count));
+            if *stale {
+                ui.colored_label(egui::Color32::from_rgb(255, 200, 60), "Stale")
+                    .on_hover_text("The file failed to load after changing, the voxels are from the last version that loaded. They turn solid again once it loads.");
+            }
+            ui.menu_button(format!("Overhangs: {}  Scaffolding: {}", overhang_count, scaffold_count), |ui| {
+                ui.checkbox(show_overhangs, "Highlight");
+                ui.checkbox(show_scaffolding, "Show scaffolding");
+                ui.separator();
+                ui.label("Supported by:");
+                for s in Support::ALL {
+                    ui.radio_value(support, s, s.name());
+                }
+            });
+            ui.separator();
+            ui.label("Camera:");
+            ui.selectable_value(cam_mode, CameraMode::Fly, "Fly");
+            ui.selectable_value(cam_mode, CameraMode::Orbit, "Orbit");
+            ui.menu_button("View", |ui| {
+                for (i, preset) in ViewPreset::ALL.into_iter().enumerate() {
+                    if ui.button(format!("{} ({})", preset.name(), i+1)).clicked() {
+                        views.send(ViewEvent::Preset(preset));
+                        ui.close_menu();
+                    }
+                }
+                if ui.button("Perspective (0)").clicked() {
+                    views.send(ViewEvent::Perspective);
+                    ui.close_menu();
+                }
+                ui.separator();
+                if ui.button("Frame model (F)").clicked() {
+                    views.send(ViewEvent::FrameModel);
+                    ui.close_menu();
+                }
+            });
             ui.separator();
             ui.add_space(10.);
+            ui.toggle_value(clipwindow_open, "Clipping");
+            ui.toggle_value(colourwindow_open, "Colours");
+            ui.toggle_value(componentwindow_open, "Components");
+            ui.toggle_value(historywindow_open, "History");
             ui.toggle_value(ewindow_open, "Dumps");
+            ui.toggle_value(settingswindow_open, "Settings");
             ui.toggle_value(helpwindow_open, "Help");
         });
     });
 
     if refresh_state {ui_state.reload_files();}
+    if let Some(dir) = new_dir {ui_state.set_shapes_dir(dir);}
 }
 
-fn edit_window(ctx: &mut Context, ui_state: &mut UiState, schematic: &mut Schematic) {
-    let UiState {ewindow_open, ..} = ui_state;
+fn edit_window(ctx: &mut Context, ui_state: &mut UiState, schematic: &Schematic, events: &mut UiEvents) {
+    let UiState {ewindow_open, shapes_dir, current_file, save_dialog, scaffold_count, ..} = ui_state;
     egui::Window::new("Dumps")
         .open(ewindow_open)
         .resizable(false)
         .collapsible(false)
         .show(ctx, |ui| {
-            if ui.button("Dump example").clicked() {
-                Schematic::example().save_to_file("example.ron");
-                println!("Dumped");
+            if ui.button("Save example as...").clicked() {
+                save_dialog.open(SaveKind::Example, shapes_dir, "example.ron".to_string());
+            }
+            let stem = current_file.as_ref().and_then(|f| f.file_stem()).map_or("current".into(), |s| s.to_string_lossy());
+            if ui.button("Save current as...").clicked() {
+                save_dialog.open(SaveKind::Current, shapes_dir, format!("{}.ron", stem));
+            }
+            if ui.button("Save project as...").clicked() {
+                save_dialog.open(SaveKind::Project, shapes_dir, format!("{}.{}", stem, project::EXTENSION));
+            }
+            if ui.add_enabled(*scaffold_count > 0, egui::Button::new("Save scaffolding as...")).clicked() {
+                save_dialog.open(SaveKind::Scaffolding, shapes_dir, format!("{}_scaffolding.ron", stem));
+            }
+            if !save_dialog.recent().is_empty() {
+                ui.separator();
+                ui.label("Recent saves:");
+                for path in save_dialog.recent() {
+                    let name = path.file_name().unwrap_or_default().to_string_lossy();
+                    if ui.button(name).on_hover_text(format!("Open {}", path.display())).clicked() {
+                        events.opener.send(OpenFileEvent(path.clone()));
+                    }
+                }
+            }
+        }
+    );
+
+    let Some((kind, path)) = ui_state.save_dialog.show(ctx) else {return;};
+    let result = match kind {
+        SaveKind::Current => schematic.save_to_file(&path),
+        SaveKind::Example => Schematic::example().save_to_file(&path),
+        SaveKind::Project => {
+            events.projects.send(ProjectEvent::Save(path)); // needs the camera, written by the project system
+            return;
+        },
+        SaveKind::Scaffolding => {
+            events.scaffolding.send(ExportScaffoldingEvent(path));
+            return;
+        },
+    };
+    match result {
+        Ok(()) => ui_state.saved(path),
+        Err(why) => ui_state.notify_error(why, None),
+    }
+}
+
+fn notifications_system(mut ctx: EguiContexts, mut ui_state: ResMut<UiState>) {
+    if ui_state.notifications.is_empty() {
+        return;
+    }
+    let mut dismissed = None;
+    egui::Area::new("notifications")
+        .anchor(egui::Align2::RIGHT_BOTTOM, [-8., -36.])
+        .show(ctx.ctx_mut(), |ui| {
+            for (i, n) in ui_state.notifications.iter().enumerate() {
+                egui::Frame::popup(ui.style()).show(ui, |ui| {
+                    ui.set_max_width(420.);
+                    ui.horizontal(|ui| {
+                        if ui.small_button("x").clicked() {dismissed = Some(i);}
+                        let color = if n.error {egui::Color32::from_rgb(255, 90, 90)} else {ui.visuals().text_color()};
+                        ui.colored_label(color, n.text.as_str());
+                    });
+                });
+            }
+        });
+    if let Some(i) = dismissed {
+        ui_state.notifications.remove(i);
+    }
+}
+
+fn status_bar_system(
+    mut ctx: EguiContexts,
+    mut picked: ResMut<Picked>,
+    mut measure: ResMut<Measure>,
+    voxels: Res<Voxels>,
+    history: Res<History>,
+    mut undoer: EventWriter<HistoryEvent>
+) {
+    egui::TopBottomPanel::bottom("status_bar").show(ctx.ctx_mut(), |ui| {
+        if matches!(picked.tool, Tool::Add | Tool::Remove) {
+            ui.horizontal(|ui| {
+                if ui.add_enabled(history.can_undo(), egui::Button::new("Undo")).clicked() {
+                    undoer.send(HistoryEvent::Undo);
+                }
+                if ui.add_enabled(history.can_redo(), egui::Button::new("Redo")).clicked() {
+                    undoer.send(HistoryEvent::Redo);
+                }
+                ui.separator();
+                ui.label(format!("Edited voxels: {}", voxels.edits.len()));
+                ui.separator();
+                ui.label(if picked.tool == Tool::Add {"Click a face to add a voxel next to it"} else {"Click a voxel to remove it"});
+            });
+        }
+        if picked.tool == Tool::Measure {
+            ui.horizontal(|ui| {
+                match (measure.start, measure.end) {
+                    (Some(a), Some(b)) => {
+                        let d = measure.delta().unwrap_or_default();
+                        ui.label(format!("From ({}, {}, {}) to ({}, {}, {})", a.0, a.1, a.2, b.0, b.1, b.2));
+                        ui.separator();
+                        ui.label(format!("dx: {}  dy: {}  dz: {}", d.0, d.1, d.2));
+                        ui.separator();
+                        ui.label(format!("Manhattan: {}", measure.manhattan().unwrap_or_default()));
+                        ui.label(format!("Euclidean: {:.2}", measure.euclidean().unwrap_or_default()));
+                        ui.separator();
+                        ui.label(format!("Line: {} voxels, {} filled", measure.line_len().unwrap_or_default(), measure.filled));
+                    },
+                    (Some(a), None) => {ui.label(format!("From ({}, {}, {}), click the second voxel", a.0, a.1, a.2));},
+                    _ => {ui.label("Click two voxels to measure between them");},
+                }
+                if measure.start.is_some() && ui.button("Clear").clicked() {
+                    measure.clear();
+                }
+            });
+        }
+        ui.horizontal(|ui| {
+            egui::ComboBox::from_id_source("tool")
+                .selected_text(format!("Tool: {:?}", picked.tool))
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(&mut picked.tool, Tool::Inspect, "Inspect");
+                    ui.selectable_value(&mut picked.tool, Tool::Measure, "Measure");
+                    ui.selectable_value(&mut picked.tool, Tool::Add, "Add");
+                    ui.selectable_value(&mut picked.tool, Tool::Remove, "Remove");
+                });
+            ui.separator();
+            match picked.hovered {
+                Some((x, y, z)) => {
+                    ui.label(format!("Voxel: ({}, {}, {})", x, y, z));
+                    ui.separator();
+                    ui.label(format!("Layer: {}", y));
+                },
+                None => {ui.label("No voxel under the cursor");},
+            }
+            ui.separator();
+            match (picked.origin, picked.relative()) {
+                (Some(o), Some(r)) => {ui.label(format!("Origin: ({}, {}, {})  Relative: ({}, {}, {})", o.0, o.1, o.2, r.0, r.1, r.2));},
+                (Some(o), None) => {ui.label(format!("Origin: ({}, {}, {})", o.0, o.1, o.2));},
+                _ => {ui.label("Right click a voxel to set it as the origin");},
+            }
+            if picked.origin.is_some() && ui.button("Clear origin").clicked() {
+                picked.origin = None;
+            }
+        });
+    });
+}
+
+fn history_window_system(
+    mut ctx: EguiContexts,
+    mut ui_state: ResMut<UiState>,
+    history: Res<History>,
+    mut writer: EventWriter<HistoryEvent>
+) {
+    egui::Window::new("History")
+        .open(&mut ui_state.historywindow_open)
+        .resizable(false)
+        .show(ctx.ctx_mut(), |ui| {
+            ui.horizontal(|ui| {
+                if ui.add_enabled(history.can_undo(), egui::Button::new("Undo")).clicked() {writer.send(HistoryEvent::Undo);}
+                if ui.add_enabled(history.can_redo(), egui::Button::new("Redo")).clicked() {writer.send(HistoryEvent::Redo);}
+            });
+            ui.separator();
+            egui::ScrollArea::vertical().max_height(300.).show(ui, |ui| {
+                for (i, state) in history.states().iter().enumerate().rev() {
+                    let text = format!("{}  -  {} voxels", state.label, state.voxel_count());
+                    if ui.selectable_label(i == history.current(), text).clicked() {
+                        writer.send(HistoryEvent::Goto(i));
+                    }
+                }
+            });
+        }
+    );
+}
+
+fn colours_window_system(mut ctx: EguiContexts, mut ui_state: ResMut<UiState>, mut colouring: ResMut<Colouring>, schematic: Res<Schematic>) {
+    let mut edited = colouring.clone(); // so Colouring only shows as changed when something was, that paints every voxel again
+    egui::Window::new("Colours")
+        .open(&mut ui_state.colourwindow_open)
+        .resizable(false)
+        .show(ctx.ctx_mut(), |ui| {
+            egui::ComboBox::from_id_source("colour_scheme")
+                .selected_text(edited.scheme.name())
+                .show_ui(ui, |ui| {
+                    for scheme in Scheme::ALL {
+                        ui.selectable_value(&mut edited.scheme, scheme, scheme.name());
+                    }
+                });
+            ui.separator();
+            match edited.scheme {
+                Scheme::Layers => {
+                    ui.horizontal(|ui| {
+                        ui.label("Layers per colour:");
+                        ui.add(egui::DragValue::new(&mut edited.band).clamp_range(1..=64));
+                    });
+                    palette_edit(ui, &mut edited.palette);
+                },
+                Scheme::Height => {
+                    ui.horizontal(|ui| {
+                        ui.label("Lowest:");
+                        ui.color_edit_button_rgb(&mut edited.low);
+                        ui.label("Highest:");
+                        ui.color_edit_button_rgb(&mut edited.high);
+                    });
+                },
+                Scheme::Normal => {
+                    ui.label("The direction to a voxel's open faces as red, green and blue for X, Y and Z. Voxels without open faces are dark grey.");
+                },
+                Scheme::Element => {
+                    ui.label("Each element of the shape gets the next palette colour.");
+                    palette_edit(ui, &mut edited.palette);
+                },
+                Scheme::Group | Scheme::Material => {
+                    let names = if edited.scheme == Scheme::Group {schematic.groups()} else {schematic.materials()};
+                    if names.is_empty() {
+                        ui.label("The loaded shape has none, only .obj files with 'g', 'o' or 'usemtl' lines do.");
+                    }
+                    for (i, name) in names.iter().enumerate() {
+                        let c = edited.palette.get(i % edited.palette.len().max(1)).copied().unwrap_or(edited.single);
+                        ui.horizontal(|ui| {
+                            let (rect, _) = ui.allocate_exact_size(egui::vec2(12., 12.), egui::Sense::hover());
+                            ui.painter().rect_filled(rect, 2., egui::Rgba::from_rgb(c[0], c[1], c[2]));
+                            ui.label(name);
+                        });
+                    }
+                    palette_edit(ui, &mut edited.palette);
+                },
+                Scheme::Component => {
+                    ui.label("Each connected part gets the next palette colour, from the largest. What counts as connected is set in the components window.");
+                    palette_edit(ui, &mut edited.palette);
+                },
+                Scheme::Single => {
+                    ui.color_edit_button_rgb(&mut edited.single);
+                },
+            }
+            if edited.scheme.needs_sources() {
+                ui.label("Voxels added by hand and elements without one are grey.");
+            }
+            ui.separator();
+            ui.horizontal(|ui| {
+                ui.label("Scaffolding:");
+                ui.color_edit_button_rgb(&mut edited.scaffold);
+            });
+        }
+    );
+    if edited != *colouring {*colouring = edited;}
+}
+
+// Deleting goes through edits, so it can be undone and survives voxelizing again
+fn components_window_system(
+    mut ctx: EguiContexts,
+    mut ui_state: ResMut<UiState>,
+    components: Res<Components>,
+    colouring: Res<Colouring>,
+    mut writer: EventWriter<EditEvent>
+) {
+    let UiState {componentwindow_open, connectivity, min_component, ..} = ui_state.as_mut();
+    let mut picked = *connectivity;
+    egui::Window::new("Components")
+        .open(componentwindow_open)
+        .resizable(false)
+        .show(ctx.ctx_mut(), |ui| {
+            ui.label("Connected by:");
+            for c in Connectivity::ALL {
+                ui.radio_value(&mut picked, c, c.name());
             }
-            if ui.button("Dump current").clicked() {
-                schematic.save_to_file("currentdump.ron");
-                println!("Dumped");
+            ui.separator();
+            let sizes = components.sizes();
+            ui.label(format!("{} components", sizes.len()));
+            let row_height = ui.text_style_height(&egui::TextStyle::Body);
+            egui::ScrollArea::vertical().max_height(200.).show_rows(ui, row_height, sizes.len(), |ui, rows| {
+                for i in rows {
+                    ui.horizontal(|ui| {
+                        if colouring.scheme == Scheme::Component && !colouring.palette.is_empty() {
+                            let c = colouring.palette[i % colouring.palette.len()];
+                            let (rect, _) = ui.allocate_exact_size(egui::vec2(12., 12.), egui::Sense::hover());
+                            ui.painter().rect_filled(rect, 2., egui::Rgba::from_rgb(c[0], c[1], c[2]));
+                        }
+                        ui.label(format!("#{}  -  {} voxels", i + 1, sizes[i]));
+                    });
+                }
+            });
+            ui.separator();
+            ui.horizontal(|ui| {
+                ui.label("Smaller than:");
+                ui.add(egui::DragValue::new(min_component).clamp_range(1..=usize::MAX));
+                let small: usize = sizes.iter().filter(|&&s| s < *min_component).sum();
+                if ui.add_enabled(small > 0, egui::Button::new(format!("Delete {} voxels", small))).clicked() {
+                    for p in components.smaller_than(*min_component) {
+                        writer.send(EditEvent {pos: p, filled: false});
+                    }
+                }
+            });
+        }
+    );
+    if picked != *connectivity {*connectivity = picked;}
+}
+
+// Click a colour to change it, right click removes it
+fn palette_edit(ui: &mut egui::Ui, palette: &mut Vec<[f32; 3]>) {
+    ui.horizontal_wrapped(|ui| {
+        ui.label("Palette:");
+        let mut removed = None;
+        for (i, c) in palette.iter_mut().enumerate() {
+            if ui.color_edit_button_rgb(c).on_hover_text("Right click to remove").secondary_clicked() {removed = Some(i);}
+        }
+        if let Some(i) = removed {
+            if palette.len() > 1 {palette.remove(i);}
+        }
+        if ui.button("+").clicked() {
+            palette.push(palette.last().copied().unwrap_or([1., 1., 1.]));
+        }
+        if ui.button("Reset").clicked() {
+            *palette = Colouring::default().palette;
+        }
+    });
+}
+
+// Changes apply right away, 'Save' writes them to the settings file
+fn settings_window_system(
+    mut ctx: EguiContexts,
+    mut ui_state: ResMut<UiState>,
+    mut settings: ResMut<Settings>,
+    mut capture: ResMut<KeyCapture>,
+    windows: Query<&Window, With<PrimaryWindow>>
+) {
+    let mut edited = settings.clone(); // so Settings only shows as changed when something was
+    let mut save = false;
+    egui::Window::new("Settings")
+        .open(&mut ui_state.settingswindow_open)
+        .resizable(false)
+        .show(ctx.ctx_mut(), |ui| {
+            egui::Grid::new("settings_grid").show(ui, |ui| {
+                ui.label("Camera speed");
+                ui.add(egui::DragValue::new(&mut edited.cam_speed).speed(0.5).clamp_range(0.1..=1000.));
+                ui.end_row();
+                ui.label("Speed boost");
+                ui.add(egui::DragValue::new(&mut edited.shift_boost).speed(0.1).clamp_range(1.0..=100.));
+                ui.end_row();
+                ui.label("Mouse sensitivity");
+                ui.add(egui::DragValue::new(&mut edited.mouse_sens).speed(0.0001).clamp_range(0.0001..=0.1).max_decimals(4));
+                ui.end_row();
+                ui.label("Window size");
+                ui.horizontal(|ui| {
+                    ui.add(egui::DragValue::new(&mut edited.window_size.0).clamp_range(200.0..=10000.));
+                    ui.add(egui::DragValue::new(&mut edited.window_size.1).clamp_range(200.0..=10000.));
+                    if let Ok(window) = windows.get_single() {
+                        if ui.button("Use current").clicked() {edited.window_size = (window.width(), window.height());}
+                    }
+                });
+                ui.end_row();
+            });
+            ui.separator();
+            ui.strong("Keys");
+            egui::Grid::new("key_grid").show(ui, |ui| {
+                for action in Action::ALL {
+                    ui.label(action.name());
+                    ui.horizontal(|ui| {
+                        let keys = edited.keys.keys_mut(action);
+                        let mut removed = None;
+                        for (i, key) in keys.iter().enumerate() {
+                            let text = if capture.0 == Some((action, Some(i))) {"Press a key...".to_string()} else {format!("{:?}", key)};
+                            let button = ui.button(text).on_hover_text("Click to change, right click to remove");
+                            // without focus space or enter can't click it again while being bound
+                            if button.clicked() {button.surrender_focus(); capture.0 = Some((action, Some(i)));}
+                            if button.secondary_clicked() {removed = Some(i);}
+                        }
+                        if let Some(i) = removed {keys.remove(i);}
+                        let adding = capture.0 == Some((action, None));
+                        let button = ui.button(if adding {"Press a key..."} else {"+"});
+                        if button.clicked() {button.surrender_focus(); capture.0 = Some((action, None));}
+                    });
+                    ui.end_row();
+                }
+            });
+            ui.label("Escape cancels binding a key.");
+            ui.separator();
+            ui.horizontal(|ui| {
+                save = ui.button("Save").clicked();
+                if ui.button("Reset to defaults").clicked() {edited = Settings::default();}
+            });
+        }
+    );
+
+    if !ui_state.settingswindow_open && capture.0.is_some() {capture.0 = None;}
+    if edited != *settings {*settings = edited;}
+    if save {
+        match settings.save() {
+            Ok(()) => ui_state.notify_info(format!("Saved settings to {}", crate::consts::SETTINGS_PATH)),
+            Err(why) => ui_state.notify_error(why, None),
+        }
+    }
+}
+
+fn clip_window(ctx: &mut Context, ui_state: &mut UiState, rot_con: &RotationConfig) {
+    let UiState {clipwindow_open, clip, ..} = ui_state;
+    let (min, max) = rot_con.bounds;
+    let ranges = [(min.0, max.0), (min.1, max.1), (min.2, max.2)];
+    egui::Window::new("Clipping")
+        .open(clipwindow_open)
+        .resizable(false)
+        .collapsible(false)
+        .show(ctx, |ui| {
+            egui::Grid::new("clip_grid").show(ui, |ui| {
+                ui.label("");
+                ui.label("Min");
+                ui.label("Max");
+                ui.label("Preview layers");
+                ui.end_row();
+                for ((name, c), (lo, hi)) in ["X", "Y", "Z"].into_iter().zip(clip.iter_mut()).zip(ranges) {
+                    ui.label(name);
+                    ui.add(egui::Slider::new(&mut c.min, lo..=(hi+1)).integer());
+                    ui.add(egui::Slider::new(&mut c.max, (lo-1)..=hi).integer());
+                    ui.add(egui::DragValue::new(&mut c.preview).clamp_range(0..=16));
+                    ui.end_row();
+                }
+            });
+            if ui.button("Show all").clicked() {
+                for (c, (lo, hi)) in clip.iter_mut().zip(ranges) {
+                    c.min = lo;
+                    c.max = hi;
+                }
             }
         }
     );
@@ -181,24 +880,62 @@ fn help_window(ctx: &mut Context, ui_state: &mut UiState) {
         .show(ctx, |ui| {
             ui.strong("Menu:");
             ui.label(
-r#" - Load Schematic:  Load an .obj or .ron file holding a shape. The file must be in the ./shapes/ folder. 'Refresh' refreshes the list if a new file is put into or removed from the folder while the program is running.
- - Reset Rotations:  Resets rotations and scale to 0, 0, 0, 1.0.
+r#" - Load Schematic:  Load an .obj or .ron file holding a shape from the shapes folder, subfolders show up as submenus. The folder is './shapes' unless changed with 'Folder' in the menu or by passing a folder on the command line. Files can also be passed on the command line or dropped onto the window, those from outside the folder are listed at the top of the menu. Dropping a folder makes it the shapes folder. 'Refresh' refreshes the list.
+ - Reset Rotations:  Resets rotations, scale and offset to 0, 0, 0, (1.0, 1.0, 1.0), (0, 0, 0) and turns off mirroring.
  - Rotations (ypr):  Rotate the shape by Yaw, Pitch and Roll in degrees.
- - Scale:  Scale the shape by a factor. The sizes of shapes at scale 1.0 can vary greatly, use this to accommodate.
- - Up to Y:  Select Y level to render up to. The top two layers are transparent.
- - Reload Voxels:  After changing rotation, scale, or 'up to Y' use this to apply the changes.
- - Dumps:  Opens the schematic dump window.
-'Dump example' dumps an example .ron file named 'example.ron' that showcases how to create custom schematics.
-'Dump current' dumps the current loaded shape into a .ron schematic named 'currentdump.ron' - used mostly for debugging.
+ - Scale:  Scale the shape by a factor along X, Y and Z. The sizes of shapes at scale 1.0 can vary greatly, use this to accommodate. Different values per axis stretch the shape.
+ - Mirror:  Flip the shape along the X, Y or Z axis, e.g. to build the mirror image for the opposite side.
+ - Offset:  Move the shape by X, Y and Z in voxels. Fractional values shift the shape within a voxel, which changes how it gets quantized.
+ - Centring:  'Centre on voxel' moves the centre of the shape to the centre of a voxel, 'Centre on corner' moves it to a voxel corner. Symmetric shapes give symmetric results with one of these. The offset is applied afterwards.
+ - Up to Y:  Select Y level to render up to. The layers above it are transparent, two by default.
+ - Clipping:  Opens the clipping window. It sets the min and max rendered level along X, Y and Z, to look at cross-sections or inside walls. 'Preview layers' is how many transparent layers are shown past the max on that axis.
+ - Colours:  Picks how voxels are coloured. 'By layer' goes through the palette every few layers, set with 'Layers per colour'. 'Height gradient' blends from the lowest to the highest layer. 'Normal direction' shows which way the open faces of a voxel point. 'By element', 'By group' and 'By material' colour voxels by the triangle or other element they came from, or its group or material in .obj files, picking one of these works that out in the background for the voxels shown, without voxelizing again, so edits and transform settings not yet applied stay as they are. 'By component' gives each connected part its own colour, see Components. 'Single colour' colours everything the same. Clipped layers stay transparent in all of them.
+ - Overhangs:  The number of voxels with nothing holding them up, for 3D printing or building in survival. Click it to pick what counts as support: only the voxel straight below, also the four next to that one, like a 45 degree overhang, or also the diagonal ones. The lowest layer stands on the ground. 'Highlight' paints them magenta, over any colouring. 'Show scaffolding' adds pillars under each of them, down to the model or the lowest layer, in the scaffolding colour set in the colours window. The number of scaffolding blocks is shown next to the overhangs, the scaffolding can be saved on its own from the dumps window.
+ - Components:  Lists the connected parts of the voxels by size, largest first. Voxels are connected if they share a face, with '18' also an edge and with '26' also a corner. 'Delete' removes every part smaller than the size next to it, like specks left by noisy meshes, as one step in the history. The 'By component' colouring gives each part its own colour.
+ - Overlay:  Draws the triangles of the shape the voxels shown were made from over them, as a wireframe or a translucent surface, to see where the voxels differ from it. Uses the same transform as the voxels, so while a newly loaded shape or changed settings are still voxelizing it keeps showing the old ones.
+ - Reload Voxels:  Voxelizes the shape again. With 'Auto' ticked this happens by itself shortly after rotation, scale, mirroring, offset or centring stop changing. 'Up to Y' and clipping only change which voxels are shown and apply right away without voxelizing. Voxelization runs in the background, a progress bar shows how many triangles are done and 'Cancel' stops it. The previous voxels stay visible until the new ones are ready.
+ - Dumps:  Opens the schematic dump window, it lists the recent saves, click one to open it.
+'Save example as...' saves an example .ron file that showcases how to create custom schematics.
+'Save current as...' saves the current loaded shape as a .ron schematic - used mostly for debugging or to convert an .obj.
+'Save project as...' saves an .ultvox project, see Projects.
+'Save scaffolding as...' saves the scaffolding as a .ron schematic of points, one per block, loading it gives back the same blocks.
+All open a save dialog: click a folder to open it, 'Up' goes to the parent folder, or type a folder in 'Folder'. Clicking an existing file or a recent save takes its name, the name can include subfolders and '.ron' is added if it has no extension. Saving over an existing file asks for confirmation first. The dialog opens in the folder of the last save, the shapes folder until then. That folder and the recent saves are kept in 'save_dialog.ron' in the working folder for the next run.
+ - Projects:  A project stores which shape file to load along with the rotation, scale, mirroring, offset, centring, clipping, voxel edits and camera. Projects show up in the load menu next to shapes and can be passed on the command line or dropped onto the window like them. Opening one loads the shape, voxelizes it with those settings and puts the edits and the camera back once it's done. The shape's path is stored relative to the project if it's in the project's folder or below it, so they can be moved together.
+ - History:  Every voxelization, loaded shape and voxel edit is kept as a step, with its voxel count. Click a step to go back to it, its rotation, scale, mirroring, offset, centring, shape and edits are restored without voxelizing again. Ctrl+Z undoes, Ctrl+Y or Ctrl+Shift+Z redoes, making a change after undoing drops the steps after it.
+ - Files:  The files in the shapes folder are watched, the load menu updates when files are added or removed. When the loaded file is saved it's loaded and voxelized again with the current settings. If it can't be read the error is shown, the voxels of the last version that loaded turn transparent and 'Stale' shows next to the voxel count until the file loads again.
+ - Errors:  Files that can't be read or saved, parse errors with their line and column and a missing shapes folder show up in the bottom right corner. Click 'x' to dismiss them, errors about a file go away by themselves once it loads. Loaded files are checked for elements that can't be voxelized: undefined or NaN points, zero area triangles and polygons, polygons with less than 3 points and negative sphere sizes. Those elements are skipped and listed by their index.
+ - Settings:  Camera speed, speed boost, mouse sensitivity, window size and the camera keys. Changes apply right away, 'Save' writes them to 'settings.ron' in the working folder, which is loaded on start. Click a key to bind another one in its place, '+' adds a key to an action and right clicking a key removes it.
  - Help:  Opens this window. It's very helpful.
 "#
+            );
+            ui.strong("Picking:");
+            ui.label(
+r#" - The voxel under the cursor is highlighted, the status bar at the bottom shows its coordinates and layer.
+ - Right click a voxel to make it the origin, coordinates relative to it are shown next to the hovered voxel's.
+ - Only voxels that aren't clipped away can be picked.
+ - Tool:  What left clicking a voxel does, dragging to rotate the orbit camera doesn't count as a click.
+   'Inspect' only shows coordinates.
+   'Measure' picks two voxels and shows dx/dy/dz, the Manhattan and Euclidean distance between their centres, and how many voxels the straight line between them passes through and how many of those are filled. The line is drawn in yellow, a third click starts a new measurement.
+   'Add' fills the voxel in front of the clicked face, 'Remove' clears the clicked voxel. Undo and Redo are in the status bar and the history window.
+ - Edits are kept when voxelizing again with the same rotation, scale, mirroring, offset and centring, they're dropped when any of those change or another shape is loaded."#
             );
             ui.strong("Camera:");
             ui.label(
-r#" - Press 'Z' to toggle between menu mode and camera control. Using the camera captures the cursor.
+r#"There are two camera modes, selected with 'Camera' in the menu.
+Fly:
+ - Press 'Z' to toggle between menu mode and camera control. Using the camera captures the cursor. Pressing 'Z' in orbit mode switches to the fly camera.
  - Use WASD to move forward/backward and to the sides. Arrow keys also work.
  - Spacebar and Control can be used to move the camera up and down as well.
- - Hold Shift to increase the camera movement speed by 4.0 times"#
+ - Hold Shift to increase the camera movement speed by 4.0 times
+ - These are the default keys and speeds, they can be changed in Settings.
+Orbit:
+ - Works in menu mode without capturing the cursor, the camera circles around the centre of the voxels.
+ - Drag with the left mouse button to rotate, drag with the middle mouse button to pan.
+ - Use the mouse wheel to zoom in and out.
+View:
+ - Orthographic top, bottom, front, back, left, right and isometric views, also on keys 1 to 7 in menu mode. Key 0 goes back to perspective.
+ - 'Frame model' (key F) centres the camera on the voxels and zooms so they all fit in view.
+ - Picking a view switches to the orbit camera, the fly camera always uses perspective."#
             );
         }
     );
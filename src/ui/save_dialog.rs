@@ -0,0 +1,281 @@
+use std::path::{Path, PathBuf};
+
+use bevy_egui::egui;
+
+use crate::{general_sys::project, ron_file::{self, FileError}};
+
+const RECENT_LEN: usize = 8;
+
+// What gets written once a path is picked, the writing itself is up to the caller
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SaveKind {
+    Current, // the loaded schematic
+    Example,
+    Project,
+    Scaffolding
+}
+
+impl SaveKind {
+    fn title(&self) -> &'static str {
+        match self {
+            Self::Current => "Save current schematic",
+            Self::Example => "Save example schematic",
+            Self::Project => "Save project",
+            Self::Scaffolding => "Save scaffolding",
+        }
+    }
+
+    fn extension(&self) -> &'static str {
+        match self {
+            Self::Current | Self::Example | Self::Scaffolding => "ron",
+            Self::Project => project::EXTENSION,
+        }
+    }
+}
+
+// File name prompt with a folder browser, shown while a save is in progress
+#[derive(Default)]
+pub struct SaveDialog {
+    kind: Option<SaveKind>, // open while Some
+    dir: Option<PathBuf>, // last folder browsed or saved to, kept between saves
+    dir_edit: String,
+    name: String,
+    listing: Listing,
+    overwrite: Option<PathBuf>, // existing file waiting for the overwrite to be confirmed
+    recent: Vec<PathBuf>, // most recent first, at most RECENT_LEN
+    store: Option<PathBuf> // where dir and recent are kept between runs, not kept if None
+}
+
+// What's kept of the dialog between runs
+#[derive(serde::Serialize, serde::Deserialize, Default, Debug, PartialEq)]
+#[serde(default)]
+struct Remembered {
+    dir: Option<PathBuf>,
+    recent: Vec<PathBuf>
+}
+
+// Contents of the dialog's folder, read when the folder changes
+#[derive(Default)]
+struct Listing {
+    folders: Vec<String>,
+    files: Vec<String>, // only those with the extension being saved
+    error: Option<String>
+}
+
+impl Listing {
+    fn read(dir: &Path, extension: &str) -> Self {
+        let entries = match std::fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(why) => return Self {error: Some(format!("couldn't read {}: {}", dir.display(), why)), ..Self::default()},
+        };
+        let mut ret = Self::default();
+        for entry in entries.flatten() {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            if entry.file_type().is_ok_and(|t| t.is_dir()) {
+                ret.folders.push(name);
+            }
+            else if Path::new(&name).extension().is_some_and(|e| e == extension) {
+                ret.files.push(name);
+            }
+        }
+        ret.folders.sort();
+        ret.files.sort();
+        ret
+    }
+}
+
+impl SaveDialog {
+    // Picks up the folder and recent saves of earlier runs, a missing or broken file just starts without them
+    pub fn load(store: &Path) -> Self {
+        let Remembered {dir, recent} = ron_file::read_ron(store).unwrap_or_default();
+        Self {dir, recent, store: Some(store.to_path_buf()), ..Self::default()}
+    }
+
+    // The folder from the last save is used if there was one
+    pub fn open(&mut self, kind: SaveKind, default_dir: &Path, name: String) {
+        self.kind = Some(kind);
+        self.name = name;
+        self.overwrite = None;
+        let dir = self.dir.clone().unwrap_or_else(|| default_dir.to_path_buf());
+        self.set_dir(dir);
+    }
+
+    pub fn recent(&self) -> &[PathBuf] {
+        &self.recent
+    }
+
+    // Call after writing to the path show() returned, closes the dialog
+    // Errs if the folder and recent saves couldn't be kept for the next run
+    pub fn saved(&mut self, path: PathBuf) -> Result<(), FileError> {
+        self.kind = None;
+        self.overwrite = None;
+        if let Some(dir) = path.parent() {
+            self.dir = Some(dir.to_path_buf());
+        }
+        self.recent.retain(|p| *p != path);
+        self.recent.insert(0, path);
+        self.recent.truncate(RECENT_LEN);
+        match &self.store {
+            Some(store) => ron_file::write_ron(&Remembered {dir: self.dir.clone(), recent: self.recent.clone()}, store),
+            None => Ok(()),
+        }
+    }
+
+    fn set_dir(&mut self, dir: PathBuf) {
+        self.dir_edit = dir.display().to_string();
+        self.listing = Listing::read(&dir, self.kind.map_or("ron", |k| k.extension()));
+        self.dir = Some(dir);
+    }
+
+    // The name can include subfolders, the extension is added if it's missing
+    fn target(&self) -> Option<PathBuf> {
+        let name = self.name.trim();
+        if name.is_empty() || name.ends_with(['/', '\\']) {
+            return None;
+        }
+        let mut path = self.dir.clone().unwrap_or_default().join(name);
+        if path.extension().is_none() {
+            path.set_extension(self.kind.map_or("ron", |k| k.extension()));
+        }
+        Some(path)
+    }
+
+    // Returns the path to write to once Save is clicked, and for existing files the overwrite confirmed
+    // The dialog stays open until saved() is called, so a failed save can be retried
+    pub fn show(&mut self, ctx: &egui::Context) -> Option<(SaveKind, PathBuf)> {
+        let kind = self.kind?;
+        let target = self.target();
+        if self.overwrite.is_some() && self.overwrite != target {
+            self.overwrite = None; // the name changed since
+        }
+        let mut open = true;
+        let mut ret = None;
+        let mut new_dir = None;
+        let mut new_name = None;
+        egui::Window::new(kind.title())
+            .open(&mut open)
+            .resizable(false)
+            .collapsible(false)
+            .show(ctx, |ui| {
+                let dir = self.dir.clone().unwrap_or_default();
+                ui.horizontal(|ui| {
+                    ui.label("Folder:");
+                    let edit = ui.text_edit_singleline(&mut self.dir_edit);
+                    if edit.lost_focus() && Path::new(self.dir_edit.as_str()) != dir {
+                        new_dir = Some(PathBuf::from(self.dir_edit.as_str()));
+                    }
+                    // relative folders like '.' have no parent until made absolute
+                    let up = std::fs::canonicalize(&dir).ok().and_then(|d| d.parent().map(Path::to_path_buf));
+                    if ui.add_enabled(up.is_some(), egui::Button::new("Up")).clicked() {
+                        new_dir = up;
+                    }
+                });
+                ui.separator();
+                egui::ScrollArea::vertical().max_height(200.).show(ui, |ui| {
+                    if let Some(why) = &self.listing.error {
+                        ui.colored_label(egui::Color32::from_rgb(255, 90, 90), why.as_str());
+                    }
+                    for folder in self.listing.folders.iter() {
+                        if ui.button(format!("{}/", folder)).clicked() {
+                            new_dir = Some(dir.join(folder));
+                        }
+                    }
+                    for file in self.listing.files.iter() {
+                        if ui.selectable_label(*file == self.name, file.as_str()).clicked() {
+                            new_name = Some(file.clone());
+                        }
+                    }
+                });
+                if !self.recent.is_empty() {
+                    ui.separator();
+                    ui.label("Recent:");
+                    for path in self.recent.iter() {
+                        let name = path.file_name().unwrap_or_default().to_string_lossy();
+                        if ui.button(name).on_hover_text(path.display().to_string()).clicked() {
+                            new_dir = path.parent().map(Path::to_path_buf);
+                            new_name = path.file_name().map(|n| n.to_string_lossy().into_owned());
+                        }
+                    }
+                }
+                ui.separator();
+                ui.horizontal(|ui| {
+                    ui.label("Name:");
+                    ui.text_edit_singleline(&mut self.name);
+                });
+                match (self.overwrite.clone(), &target) {
+                    (Some(path), _) => {
+                        ui.colored_label(egui::Color32::from_rgb(255, 200, 60), format!("{} already exists", path.display()));
+                        ui.horizontal(|ui| {
+                            if ui.button("Overwrite").clicked() {ret = Some((kind, path.clone()));}
+                            if ui.button("Cancel").clicked() {self.overwrite = None;}
+                        });
+                    },
+                    (None, target) => {
+                        if ui.add_enabled(target.is_some(), egui::Button::new("Save")).clicked() {
+                            match target {
+                                Some(path) if path.exists() => self.overwrite = Some(path.clone()),
+                                Some(path) => ret = Some((kind, path.clone())),
+                                None => {},
+                            }
+                        }
+                    },
+                }
+            });
+
+        if let Some(dir) = new_dir {self.set_dir(dir);}
+        if let Some(name) = new_name {self.name = name;}
+        if !open {self.kind = None;}
+        ret
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+    use super::{SaveDialog, SaveKind, RECENT_LEN};
+
+    #[test]
+    fn target_and_recent() {
+        let mut d = SaveDialog::default();
+        d.open(SaveKind::Current, &PathBuf::from("missing_dir"), "  ".to_string());
+        assert_eq!(d.target(), None);
+        d.name = "sub/".to_string();
+        assert_eq!(d.target(), None);
+        d.name = "sub/shape".to_string();
+        assert_eq!(d.target(), Some(PathBuf::from("missing_dir/sub/shape.ron")));
+        d.name = "shape.txt".to_string();
+        assert_eq!(d.target(), Some(PathBuf::from("missing_dir/shape.txt")));
+
+        for i in 0..RECENT_LEN {
+            d.saved(PathBuf::from(format!("a/{}.ron", i))).unwrap();
+        }
+        d.saved(PathBuf::from("b/0.ron")).unwrap();
+        d.saved(PathBuf::from("a/3.ron")).unwrap();
+        assert_eq!(d.recent().len(), RECENT_LEN);
+        assert_eq!(d.recent()[0], PathBuf::from("a/3.ron"));
+        assert_eq!(d.recent()[1], PathBuf::from("b/0.ron"));
+        assert!(!d.recent().contains(&PathBuf::from("a/0.ron")));
+
+        // the last folder saved to is where the dialog opens next time
+        d.open(SaveKind::Example, &PathBuf::from("missing_dir"), "example.ron".to_string());
+        assert_eq!(d.target(), Some(PathBuf::from("a/example.ron")));
+    }
+
+    #[test]
+    fn remembered_between_runs() {
+        let dir = std::env::temp_dir().join(format!("ultvox_dialog_test_{}", std::process::id()));
+        let store = dir.join("save_dialog.ron");
+        let mut d = SaveDialog::load(&store);
+        assert!(d.recent().is_empty());
+        d.saved(dir.join("shapes/a.ron")).unwrap();
+        d.saved(dir.join("b.ultvox")).unwrap();
+
+        let mut again = SaveDialog::load(&store);
+        assert_eq!(again.recent(), d.recent());
+        again.open(SaveKind::Current, &PathBuf::from("missing_dir"), "c".to_string());
+        assert_eq!(again.target(), Some(dir.join("c.ron")));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,241 @@
+use std::collections::BTreeMap;
+
+const CHUNK_BITS: i32 = 4; // chunks are 16x16x16 voxels
+const CHUNK: i32 = 1 << CHUNK_BITS;
+const WORDS: usize = (CHUNK*CHUNK*CHUNK) as usize / 64;
+const LAYER_WORDS: usize = WORDS / CHUNK as usize; // words holding one y layer of a chunk
+
+pub type Pos = (i32, i32, i32);
+
+type Key = (i32, i32, i32); // chunk coordinates in (y, z, x) order so chunks are sorted by layer
+
+// Set of voxel coordinates stored as a sparse map of chunked bitsets
+// Bits inside a chunk are indexed by x + z*16 + y*256, so each y layer of a chunk is 4 consecutive words
+#[derive(Clone, Default, Debug, PartialEq, Eq)]
+pub struct VoxelGrid {
+    chunks: BTreeMap<Key, Box<[u64; WORDS]>>, // never holds empty chunks
+    len: usize
+}
+
+fn split((x, y, z): Pos) -> (Key, usize) {
+    let key = (y >> CHUNK_BITS, z >> CHUNK_BITS, x >> CHUNK_BITS);
+    let (lx, ly, lz) = (x & (CHUNK-1), y & (CHUNK-1), z & (CHUNK-1));
+    (key, (lx + lz*CHUNK + ly*CHUNK*CHUNK) as usize)
+}
+
+fn join((cy, cz, cx): Key, index: usize) -> Pos {
+    let index = index as i32;
+    let (lx, lz, ly) = (index & (CHUNK-1), (index >> CHUNK_BITS) & (CHUNK-1), index >> (2*CHUNK_BITS));
+    ((cx << CHUNK_BITS) + lx, (cy << CHUNK_BITS) + ly, (cz << CHUNK_BITS) + lz)
+}
+
+// Yields the set bits of words[first..], offset so they are indices into the whole chunk
+fn bits(words: &[u64], first: usize) -> impl Iterator<Item = usize> + '_ {
+    words.iter().enumerate().flat_map(move |(i, &w)| {
+        let mut w = w;
+        std::iter::from_fn(move || {
+            if w == 0 {return None;}
+            let b = w.trailing_zeros() as usize;
+            w &= w - 1;
+            Some((first + i)*64 + b)
+        })
+    })
+}
+
+impl VoxelGrid {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    // Returns true if the voxel wasn't already in the grid
+    pub fn insert(&mut self, p: Pos) -> bool {
+        let (key, i) = split(p);
+        let word = &mut self.chunks.entry(key).or_insert_with(|| Box::new([0; WORDS]))[i/64];
+        let mask = 1 << (i%64);
+        let new = *word & mask == 0;
+        *word |= mask;
+        self.len += new as usize;
+        new
+    }
+
+    // Returns true if the voxel was in the grid
+    pub fn remove(&mut self, p: Pos) -> bool {
+        let (key, i) = split(p);
+        let Some(chunk) = self.chunks.get_mut(&key) else {return false;};
+        let mask = 1 << (i%64);
+        let had = chunk[i/64] & mask != 0;
+        chunk[i/64] &= !mask;
+        if had {
+            self.len -= 1;
+            if chunk.iter().all(|w| *w == 0) {
+                self.chunks.remove(&key);
+            }
+        }
+        had
+    }
+
+    pub fn contains(&self, p: Pos) -> bool {
+        let (key, i) = split(p);
+        match self.chunks.get(&key) {
+            Some(chunk) => chunk[i/64] & (1 << (i%64)) != 0,
+            None => false,
+        }
+    }
+
+    // Ordered by chunk, chunks go in layer order, use layer() for exact y ordering
+    pub fn iter(&self) -> impl Iterator<Item = Pos> + '_ {
+        self.chunks.iter().flat_map(|(&key, chunk)| bits(&chunk[..], 0).map(move |i| join(key, i)))
+    }
+
+    // All voxels with the given y
+    pub fn layer(&self, y: i32) -> impl Iterator<Item = Pos> + '_ {
+        let cy = y >> CHUNK_BITS;
+        let first = (y & (CHUNK-1)) as usize * LAYER_WORDS;
+        self.chunks.range((cy, i32::MIN, i32::MIN)..=(cy, i32::MAX, i32::MAX))
+            .flat_map(move |(&key, chunk)| bits(&chunk[first..first+LAYER_WORDS], first).map(move |i| join(key, i)))
+    }
+
+    // Inclusive min and max corners, None if empty
+    pub fn bounds(&self) -> Option<(Pos, Pos)> {
+        let mut it = self.iter();
+        let first = it.next()?;
+        Some(it.fold((first, first), |(min, max), p| (
+            (min.0.min(p.0), min.1.min(p.1), min.2.min(p.2)),
+            (max.0.max(p.0), max.1.max(p.1), max.2.max(p.2))
+        )))
+    }
+
+    // Min and max y, cheaper than bounds() since chunks are sorted by layer
+    pub fn yrange(&self) -> Option<(i32, i32)> {
+        let (&lo, _) = self.chunks.first_key_value()?;
+        let (&hi, _) = self.chunks.last_key_value()?;
+        let lo_y = (lo.0 << CHUNK_BITS..(lo.0+1) << CHUNK_BITS).find(|y| self.layer(*y).next().is_some())?;
+        let hi_y = (hi.0 << CHUNK_BITS..(hi.0+1) << CHUNK_BITS).rev().find(|y| self.layer(*y).next().is_some())?;
+        Some((lo_y, hi_y))
+    }
+
+    pub fn union_with(&mut self, other: &VoxelGrid) {
+        for (key, chunk) in other.chunks.iter() {
+            let mine = self.chunks.entry(*key).or_insert_with(|| Box::new([0; WORDS]));
+            for (a, b) in mine.iter_mut().zip(chunk.iter()) {
+                self.len += (b & !*a).count_ones() as usize;
+                *a |= b;
+            }
+        }
+    }
+
+    #[allow(dead_code)] // part of the set operations with union and difference, only the tests use it so far
+    pub fn intersect_with(&mut self, other: &VoxelGrid) {
+        let mut len = 0;
+        self.chunks.retain(|key, mine| {
+            let Some(chunk) = other.chunks.get(key) else {return false;};
+            for (a, b) in mine.iter_mut().zip(chunk.iter()) {
+                *a &= b;
+                len += a.count_ones() as usize;
+            }
+            mine.iter().any(|w| *w != 0)
+        });
+        self.len = len;
+    }
+
+    pub fn difference_with(&mut self, other: &VoxelGrid) {
+        let mut len = 0;
+        self.chunks.retain(|key, mine| {
+            if let Some(chunk) = other.chunks.get(key) {
+                for (a, b) in mine.iter_mut().zip(chunk.iter()) {
+                    *a &= !b;
+                }
+            }
+            let count: usize = mine.iter().map(|w| w.count_ones() as usize).sum();
+            len += count;
+            count != 0
+        });
+        self.len = len;
+    }
+}
+
+impl FromIterator<Pos> for VoxelGrid {
+    fn from_iter<I: IntoIterator<Item = Pos>>(iter: I) -> Self {
+        let mut grid = Self::new();
+        grid.extend(iter);
+        grid
+    }
+}
+
+impl Extend<Pos> for VoxelGrid {
+    fn extend<I: IntoIterator<Item = Pos>>(&mut self, iter: I) {
+        for p in iter {
+            self.insert(p);
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::VoxelGrid;
+
+    #[test]
+    fn insert_remove_contains() {
+        let mut g = VoxelGrid::new();
+        assert!(g.insert((0, 0, 0)));
+        assert!(!g.insert((0, 0, 0)));
+        assert!(g.insert((-1, -17, 33)));
+        assert!(g.insert((15, 16, -16)));
+        assert_eq!(g.len(), 3);
+        assert!(g.contains((-1, -17, 33)));
+        assert!(!g.contains((-1, -17, 32)));
+        assert!(g.remove((0, 0, 0)));
+        assert!(!g.remove((0, 0, 0)));
+        assert_eq!(g.len(), 2);
+        assert_eq!(g.bounds(), Some(((-1, -17, -16), (15, 16, 33))));
+        assert_eq!(g.yrange(), Some((-17, 16)));
+
+        let mut all: Vec<_> = g.iter().collect();
+        all.sort();
+        assert_eq!(all, vec![(-1, -17, 33), (15, 16, -16)]);
+    }
+
+    #[test]
+    fn layers() {
+        let g: VoxelGrid = [(0, 3, 0), (20, 3, -40), (5, 4, 5), (-3, -13, 2)].into_iter().collect();
+        let mut layer: Vec<_> = g.layer(3).collect();
+        layer.sort();
+        assert_eq!(layer, vec![(0, 3, 0), (20, 3, -40)]);
+        assert_eq!(g.layer(-13).collect::<Vec<_>>(), vec![(-3, -13, 2)]);
+        assert_eq!(g.layer(19).count(), 0);
+    }
+
+    #[test]
+    fn set_operations() {
+        let a: VoxelGrid = [(0, 0, 0), (1, 0, 0), (40, 2, 0)].into_iter().collect();
+        let b: VoxelGrid = [(1, 0, 0), (2, 0, 0)].into_iter().collect();
+
+        let mut u = a.clone();
+        u.union_with(&b);
+        assert_eq!(u, [(0, 0, 0), (1, 0, 0), (2, 0, 0), (40, 2, 0)].into_iter().collect());
+        assert_eq!(u.len(), 4);
+
+        let mut i = a.clone();
+        i.intersect_with(&b);
+        assert_eq!(i, [(1, 0, 0)].into_iter().collect());
+        assert_eq!(i.len(), 1);
+        // chunks left empty are dropped, so the grid equals a fresh empty one
+        i.intersect_with(&[(40, 2, 0)].into_iter().collect());
+        assert_eq!(i, VoxelGrid::new());
+        assert!(i.is_empty());
+
+        let mut d = a.clone();
+        d.difference_with(&b);
+        assert_eq!(d, [(0, 0, 0), (40, 2, 0)].into_iter().collect());
+        assert_eq!(d.len(), 2);
+    }
+}
@@ -1,14 +1,14 @@
-use std::collections::HashSet;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 
-use crate::shapes::*;
+use crate::{shapes::*, voxel_grid::VoxelGrid};
 
 pub trait Voxelizable {
-    fn voxelize(&self) -> HashSet<(i32, i32, i32)>;
+    fn voxelize(&self) -> VoxelGrid;
 }
 
 impl Voxelizable for Tri {
-    fn voxelize(&self) -> HashSet<(i32, i32, i32)> {
-        let mut set = HashSet::new();
+    fn voxelize(&self) -> VoxelGrid {
+        let mut set = VoxelGrid::new();
 
         let flatx = self.get_flat(0);
         let flaty = self.get_flat(1);
@@ -41,8 +41,8 @@ impl Voxelizable for Tri {
 }
 
 impl Voxelizable for Sphere {
-    fn voxelize(&self) -> HashSet<(i32, i32, i32)> {
-        let mut set = HashSet::new();
+    fn voxelize(&self) -> VoxelGrid {
+        let mut set = VoxelGrid::new();
         for p in self.points_inside() {
             set.insert((p.x as i32, p.y as i32, p.z as i32));
         }
@@ -51,8 +51,54 @@ impl Voxelizable for Sphere {
     }
 }
 
-pub fn merge<T>(base: &mut HashSet<T>, other: HashSet<T>) where T: Eq + std::hash::Hash {
-    for item in other {
-        base.insert(item);
+impl Voxelizable for Ellipsoid {
+    fn voxelize(&self) -> VoxelGrid {
+        let mut set = VoxelGrid::new();
+        for p in self.points_inside() {
+            set.insert((p.x as i32, p.y as i32, p.z as i32));
+        }
+
+        set
+    }
+}
+
+// Shared between a voxelization running on another thread and the ui that watches it
+pub struct Progress {
+    done: AtomicUsize,
+    total: usize,
+    cancelled: AtomicBool
+}
+
+impl Progress {
+    pub fn new(total: usize) -> Self {
+        Self {done: AtomicUsize::new(0), total, cancelled: AtomicBool::new(false)}
+    }
+
+    pub fn advance(&self, n: usize) {
+        self.done.fetch_add(n, Ordering::Relaxed);
     }
+
+    pub fn done(&self) -> usize {
+        self.done.load(Ordering::Relaxed)
+    }
+
+    pub fn total(&self) -> usize {
+        self.total
+    }
+
+    pub fn fraction(&self) -> f32 {
+        if self.total == 0 {1.} else {self.done() as f32 / self.total as f32}
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+}
+
+pub fn merge(base: &mut VoxelGrid, other: VoxelGrid) {
+    base.union_with(&other);
 }